@@ -0,0 +1,464 @@
+//! # `#[derive(CtfFields)]` for `lttng-ust-rs`.
+//! Traces a whole struct as one event, keeping the event class in sync with the struct
+//! definition.
+//!
+//! The derive adds a few associated constants describing the event class, meant to be fed to
+//! `lttng-ust-generate` from a build script, and an `emit(&self)` method firing the
+//! tracepoint. Since build scripts can only use the struct if it lives in another crate, put
+//! it in a small crate shared by your build script (as a build dependency) and your code:
+//!
+//! ```ignore
+//! #[macro_use]
+//! extern crate lttng_ust_derive;
+//!
+//! #[derive(CtfFields)]
+//! #[ctf(provider = "my_server", instance = "request_done")]
+//! pub struct RequestStats {
+//!     pub request_id: u64,
+//!     pub path: String,
+//!     pub duration: std::time::Duration,
+//!     pub status: Option<u16>,
+//! }
+//! ```
+//!
+//! Then register the class from your `build.rs`:
+//!
+//! ```ignore
+//! let mut provider = Provider::new(RequestStats::CTF_PROVIDER);
+//! provider.create_class(RequestStats::CTF_CLASS)
+//!     .add_derived_fields(RequestStats::CTF_FIELDS)
+//!     .instantiate(RequestStats::CTF_INSTANCE);
+//! ```
+//!
+//! and call `stats.emit()` wherever you like. `emit` links directly against the C function
+//! `lttng-ust-generate` builds for the tracepoint, so it works from any crate linked into the
+//! same binary. Since it passes exactly the fields of the struct, the class can't have any
+//! other field (including the provider's common fields) nor a caller location, and its
+//! instances can't be sampled or rate limited; `lttng-ust-generate` refuses to generate such
+//! a class.
+//!
+//! The `ctf` attribute takes the `provider` (required), the `class` (defaults to the struct
+//! name in `snake_case`) and the `instance` (defaults to the class name).
+//!
+//! Supported field types are the integer types (including 128-bit ones), `f32`, `f64`, `bool`,
+//! `char`, `Duration`, `Ipv4Addr` and `Ipv6Addr`, `Option`s of those, `String` and `&str`,
+//! and `Vec`s, slices and arrays of integers up to 64 bits.
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{Data, DeriveInput, Expr, Fields, GenericArgument, Ident, Lit, LitStr, PathArguments, Type};
+
+/// See the [crate level documentation](index.html).
+#[proc_macro_derive(CtfFields, attributes(ctf))]
+pub fn derive_ctf_fields(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// How a single struct field is declared in the schema and handed to C
+struct FieldMapping {
+    /// The field type, spelled like in `lttng-ust-generate` schema files
+    schema_type: String,
+    /// Types of the C function parameters the field is passed as
+    c_params: Vec<TokenStream2>,
+    /// Expressions for those parameters
+    args: Vec<TokenStream2>,
+    /// Statement that needs to run before the arguments can be evaluated
+    binding: Option<TokenStream2>,
+}
+
+/// Types which are passed to C by value
+enum Scalar {
+    Int(Ident),
+    Float(&'static str, Ident),
+    Bool,
+    Char,
+    U128,
+    I128,
+    Duration,
+    Ipv4Addr,
+    Ipv6Addr,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let mut provider = None;
+    let mut class = None;
+    let mut instance = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("ctf")) {
+        attr.parse_nested_meta(|meta| {
+            let value = meta.value()?.parse::<LitStr>()?.value();
+            if meta.path.is_ident("provider") {
+                provider = Some(value);
+            } else if meta.path.is_ident("class") {
+                class = Some(value);
+            } else if meta.path.is_ident("instance") {
+                instance = Some(value);
+            } else {
+                return Err(meta.error("expected `provider`, `class` or `instance`"));
+            }
+            Ok(())
+        })?;
+    }
+    let provider = provider.ok_or_else(|| {
+        syn::Error::new_spanned(name, "missing `#[ctf(provider = \"...\")]`")
+    })?;
+    let class = class.unwrap_or_else(|| snake_case(&name.to_string()));
+    let instance = instance.unwrap_or_else(|| class.clone());
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(name, "CtfFields needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(name, "CtfFields can only be derived for structs")),
+    };
+
+    let mut schema = Vec::new();
+    let mut c_params = Vec::new();
+    let mut args = Vec::new();
+    let mut bindings = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let ident = field.ident.as_ref().expect("named field");
+        let mapping = map_field(&field.ty, quote!(self.#ident), i).ok_or_else(|| {
+            syn::Error::new_spanned(&field.ty, "unsupported field type for CtfFields")
+        })?;
+        let field_name = ident.to_string();
+        let schema_type = mapping.schema_type;
+        schema.push(quote!((#field_name, #schema_type)));
+        c_params.extend(mapping.c_params);
+        args.extend(mapping.args);
+        bindings.extend(mapping.binding);
+    }
+    let param_names: Vec<Ident> = (0..c_params.len())
+        .map(|i| Ident::new(&format!("a{}", i), Span::call_site()))
+        .collect();
+
+    // Matches what lttng-ust-generate calls the C function behind the tracepoint
+    let native = Ident::new(&format!("{}_{}_{}_tp", provider, class, instance), Span::call_site());
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// The lttng-ust provider this struct is traced under
+            pub const CTF_PROVIDER: &'static str = #provider;
+            /// The event class describing this struct
+            pub const CTF_CLASS: &'static str = #class;
+            /// The tracepoint `emit` fires
+            pub const CTF_INSTANCE: &'static str = #instance;
+            /// `(name, type)` for every field, for `EventClass::add_derived_fields`
+            pub const CTF_FIELDS: &'static [(&'static str, &'static str)] = &[#(#schema),*];
+
+            /// Fire the tracepoint with the contents of this struct
+            #[inline]
+            pub fn emit(&self) {
+                extern "C" {
+                    fn #native(#(#param_names: #c_params),*);
+                }
+                #(#bindings)*
+                unsafe { #native(#(#args),*) }
+            }
+        }
+    })
+}
+
+fn map_field(ty: &Type, value: TokenStream2, index: usize) -> Option<FieldMapping> {
+    if let Some(scalar) = scalar(ty) {
+        let (c_params, args) = pass_scalar(&scalar, &value);
+        return Some(FieldMapping {
+            schema_type: scalar_schema_type(&scalar),
+            c_params,
+            args,
+            binding: None,
+        });
+    }
+    if let Some(inner) = generic_arg(ty, "Option") {
+        let scalar = scalar(inner)?;
+        let present = Ident::new(&format!("present{}", index), Span::call_site());
+        let inner_value = Ident::new(&format!("value{}", index), Span::call_site());
+        let zero = zero_value(&scalar);
+        let (c_params, args) = pass_scalar(&scalar, &quote!(#inner_value));
+        return Some(FieldMapping {
+            schema_type: format!("optional {}", scalar_schema_type(&scalar)),
+            c_params: std::iter::once(quote!(u8)).chain(c_params).collect(),
+            args: std::iter::once(quote!(#present)).chain(args).collect(),
+            binding: Some(quote! {
+                let (#present, #inner_value) = match #value {
+                    Some(v) => (1u8, v),
+                    None => (0u8, #zero),
+                };
+            }),
+        });
+    }
+    if is_text(ty) {
+        return Some(FieldMapping {
+            schema_type: "sequence_text".into(),
+            c_params: vec![quote!(*const u8), quote!(usize)],
+            args: vec![quote!(#value.as_ptr()), quote!(#value.len())],
+            binding: None,
+        });
+    }
+    if let Some((element, len)) = array(ty) {
+        let int = integer(element)?;
+        return Some(FieldMapping {
+            schema_type: format!("array {} {}", int, len),
+            c_params: vec![quote!(*const #int)],
+            args: vec![quote!(#value.as_ptr())],
+            binding: None,
+        });
+    }
+    if let Some(element) = sequence(ty) {
+        let int = integer(element)?;
+        return Some(FieldMapping {
+            schema_type: format!("sequence {}", int),
+            c_params: vec![quote!(*const #int), quote!(usize)],
+            args: vec![quote!(#value.as_ptr()), quote!(#value.len())],
+            binding: None,
+        });
+    }
+    None
+}
+
+fn scalar(ty: &Type) -> Option<Scalar> {
+    if let Some(int) = integer(ty) {
+        return Some(Scalar::Int(int));
+    }
+    let ident = last_segment(ty)?.to_string();
+    let scalar = match &ident[..] {
+        "f32" => Scalar::Float("single", Ident::new("f32", Span::call_site())),
+        "f64" => Scalar::Float("double", Ident::new("f64", Span::call_site())),
+        "bool" => Scalar::Bool,
+        "char" => Scalar::Char,
+        "u128" => Scalar::U128,
+        "i128" => Scalar::I128,
+        "Duration" => Scalar::Duration,
+        "Ipv4Addr" => Scalar::Ipv4Addr,
+        "Ipv6Addr" => Scalar::Ipv6Addr,
+        _ => return None,
+    };
+    Some(scalar)
+}
+
+fn scalar_schema_type(scalar: &Scalar) -> String {
+    match *scalar {
+        Scalar::Int(ref int) => format!("integer {}", int),
+        Scalar::Float(name, _) => format!("float {}", name),
+        Scalar::Bool => "bool".into(),
+        Scalar::Char => "char".into(),
+        Scalar::U128 => "u128".into(),
+        Scalar::I128 => "i128".into(),
+        Scalar::Duration => "duration".into(),
+        Scalar::Ipv4Addr => "ipv4_addr".into(),
+        Scalar::Ipv6Addr => "ipv6_addr".into(),
+    }
+}
+
+/// The C parameter types and argument expressions for a scalar `value`.
+/// These mirror what lttng-ust-generate does for the same field types.
+fn pass_scalar(scalar: &Scalar, value: &TokenStream2) -> (Vec<TokenStream2>, Vec<TokenStream2>) {
+    match *scalar {
+        Scalar::Int(ref int) => (vec![quote!(#int)], vec![quote!(#value)]),
+        Scalar::Float(_, ref float) => (vec![quote!(#float)], vec![quote!(#value)]),
+        Scalar::Bool => (vec![quote!(u8)], vec![quote!(#value as u8)]),
+        Scalar::Char => (vec![quote!(u32)], vec![quote!(#value as u32)]),
+        Scalar::U128 => (vec![quote!(u64), quote!(u64)],
+                         vec![quote!((#value >> 64) as u64), quote!(#value as u64)]),
+        Scalar::I128 => (vec![quote!(i64), quote!(u64)],
+                         vec![quote!((#value >> 64) as i64), quote!(#value as u64)]),
+        Scalar::Duration => (vec![quote!(u64)], vec![quote! {
+            #value.as_secs().saturating_mul(1_000_000_000).saturating_add(u64::from(#value.subsec_nanos()))
+        }]),
+        Scalar::Ipv4Addr | Scalar::Ipv6Addr => (vec![quote!(*const u8)], vec![quote!(#value.octets().as_ptr())]),
+    }
+}
+
+fn zero_value(scalar: &Scalar) -> TokenStream2 {
+    match *scalar {
+        Scalar::Int(_) | Scalar::U128 | Scalar::I128 => quote!(0),
+        Scalar::Float(..) => quote!(0.0),
+        Scalar::Bool => quote!(false),
+        Scalar::Char => quote!('\0'),
+        Scalar::Duration => quote!(::std::time::Duration::new(0, 0)),
+        Scalar::Ipv4Addr => quote!(::std::net::Ipv4Addr::new(0, 0, 0, 0)),
+        Scalar::Ipv6Addr => quote!(::std::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)),
+    }
+}
+
+/// Integer types which can also be the elements of arrays and sequences
+fn integer(ty: &Type) -> Option<Ident> {
+    let ident = last_segment(ty)?;
+    match &ident.to_string()[..] {
+        "i8" | "i16" | "i32" | "i64" | "isize" |
+        "u8" | "u16" | "u32" | "u64" | "usize" => Some(ident.clone()),
+        _ => None,
+    }
+}
+
+fn is_text(ty: &Type) -> bool {
+    match *ty {
+        Type::Reference(ref r) => last_segment(&r.elem).is_some_and(|i| i == "str"),
+        _ => last_segment(ty).is_some_and(|i| i == "String"),
+    }
+}
+
+fn array(ty: &Type) -> Option<(&Type, u64)> {
+    match *ty {
+        Type::Array(ref array) => match array.len {
+            Expr::Lit(ref lit) => match lit.lit {
+                Lit::Int(ref len) => len.base10_parse().ok().map(|len| (&*array.elem, len)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn sequence(ty: &Type) -> Option<&Type> {
+    match *ty {
+        Type::Reference(ref r) => match *r.elem {
+            Type::Slice(ref slice) => Some(&*slice.elem),
+            _ => None,
+        },
+        _ => generic_arg(ty, "Vec"),
+    }
+}
+
+/// The type argument of `Wrapper<T>`
+fn generic_arg<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = match *ty {
+        Type::Path(ref path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != wrapper {
+        return None;
+    }
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) => match args.args.first()? {
+            GenericArgument::Type(ref inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn last_segment(ty: &Type) -> Option<&Ident> {
+    match *ty {
+        Type::Path(ref path) if path.qself.is_none() => {
+            let segment = path.path.segments.last()?;
+            match segment.arguments {
+                PathArguments::None => Some(&segment.ident),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(input: DeriveInput) -> String {
+        expand(&input).unwrap().to_string()
+    }
+
+    fn error(input: DeriveInput) -> String {
+        expand(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn declares_the_c_function_lttng_ust_generate_builds() {
+        let expanded = expand_str(syn::parse_quote! {
+            #[ctf(provider = "server", class = "stats", instance = "done")]
+            struct Stats<'a> {
+                id: u64,
+                ratio: f32,
+                ok: bool,
+                grade: char,
+                total: u128,
+                delta: i128,
+                elapsed: ::std::time::Duration,
+                peer: Ipv4Addr,
+                peer6: Ipv6Addr,
+                retries: Option<u8>,
+                name: String,
+                user: &'a str,
+                digest: [u8; 4],
+                samples: Vec<i32>,
+                sizes: &'a [usize],
+            }
+        });
+        let fields = quote! {
+            pub const CTF_FIELDS: &'static [(&'static str, &'static str)] = &[
+                ("id", "integer u64"), ("ratio", "float single"), ("ok", "bool"), ("grade", "char"),
+                ("total", "u128"), ("delta", "i128"), ("elapsed", "duration"),
+                ("peer", "ipv4_addr"), ("peer6", "ipv6_addr"), ("retries", "optional integer u8"),
+                ("name", "sequence_text"), ("user", "sequence_text"), ("digest", "array u8 4"),
+                ("samples", "sequence i32"), ("sizes", "sequence usize")
+            ];
+        };
+        assert!(expanded.contains(&fields.to_string()), "{}", expanded);
+        // One parameter per C field, i.e. per entry of `EventClass::c_fields`
+        let declaration = quote! {
+            fn server_stats_done_tp(
+                a0: u64, a1: f32, a2: u8, a3: u32, a4: u64, a5: u64, a6: i64, a7: u64, a8: u64,
+                a9: *const u8, a10: *const u8, a11: u8, a12: u8, a13: *const u8, a14: usize,
+                a15: *const u8, a16: usize, a17: *const u8, a18: *const i32, a19: usize,
+                a20: *const usize, a21: usize
+            );
+        };
+        assert!(expanded.contains(&declaration.to_string()), "{}", expanded);
+        assert!(expanded.contains("impl < 'a > Stats < 'a >"), "{}", expanded);
+    }
+
+    #[test]
+    fn names_default_after_the_struct() {
+        let expanded = expand_str(syn::parse_quote! {
+            #[ctf(provider = "server")]
+            struct RequestStats {
+                id: u64,
+            }
+        });
+        assert!(expanded.contains(&quote!(pub const CTF_CLASS: &'static str = "request_stats";).to_string()));
+        assert!(expanded.contains(&quote!(pub const CTF_INSTANCE: &'static str = "request_stats";).to_string()));
+        assert!(expanded.contains(&quote!(fn server_request_stats_request_stats_tp(a0: u64);).to_string()));
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_trace() {
+        assert!(error(syn::parse_quote! { struct S { id: u64 } }).contains("missing `#[ctf(provider"));
+        assert!(error(syn::parse_quote! { #[ctf(provider = "p", level = "info")] struct S { id: u64 } })
+                .contains("expected `provider`"));
+        assert!(error(syn::parse_quote! { #[ctf(provider = "p")] struct S(u64); })
+                .contains("needs named fields"));
+        assert!(error(syn::parse_quote! { #[ctf(provider = "p")] enum S { A } })
+                .contains("only be derived for structs"));
+        assert!(error(syn::parse_quote! { #[ctf(provider = "p")] struct S { m: HashMap<u8, u8> } })
+                .contains("unsupported field type"));
+        assert!(error(syn::parse_quote! { #[ctf(provider = "p")] struct S { o: Option<String> } })
+                .contains("unsupported field type"));
+    }
+}
@@ -0,0 +1,56 @@
+//! Generate tracepoint sources from a schema file, outside of any build script.
+//!
+//! ```text
+//! lttng-ust-gen [--lib-name NAME] [--output-file-name FILE] <schema> <out-dir>
+//! ```
+//!
+//! The output directory can then be vendored and built with
+//! `Generator::compile_pregenerated`, which doesn't need libclang.
+extern crate lttng_ust_generate;
+
+use std::env;
+use std::process;
+
+use lttng_ust_generate::{schema, Generator};
+
+const USAGE: &str = "usage: lttng-ust-gen [--lib-name NAME] [--output-file-name FILE] <schema> <out-dir>";
+
+fn main() {
+    let mut generator = Generator::default();
+    let mut positional = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--lib-name" => generator = generator.generated_lib_name(expect_value(&arg, args.next())),
+            "--output-file-name" => generator = generator.output_file_name(expect_value(&arg, args.next())),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") => fail(&format!("unknown option `{}`", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() != 2 {
+        fail("expected a schema file and an output directory");
+    }
+
+    let providers = schema::parse_file(&positional[0])
+        .unwrap_or_else(|e| fail(&format!("{}: {}", positional[0], e)));
+    for provider in providers {
+        generator = generator.register_provider(provider);
+    }
+    generator.write_sources(&positional[1])
+        .unwrap_or_else(|e| fail(&e.to_string()));
+}
+
+fn expect_value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| fail(&format!("`{}` requires a value", option)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("lttng-ust-gen: {}", message);
+    eprintln!("{}", USAGE);
+    process::exit(1)
+}
@@ -0,0 +1,67 @@
+use std::env;
+use std::path::PathBuf;
+
+use ::{EventClass, Provider};
+use super::Error;
+
+/// The `cargo:` metadata key exported providers are published under.
+/// Dependents see it as `DEP_<links>_LTTNG_UST_SCHEMA`.
+pub(in super) const SCHEMA_METADATA_KEY: &str = "lttng-ust-schema";
+
+/// Schema files exported by the direct dependencies of the crate being built, in a stable order
+pub(in super) fn dependency_schemas() -> Vec<(String, PathBuf)> {
+    let suffix = format!("_{}", SCHEMA_METADATA_KEY.to_uppercase().replace('-', "_"));
+    let mut schemas: Vec<(String, PathBuf)> = env::vars_os()
+        .filter_map(|(key, value)| {
+            let key = key.into_string().ok()?;
+            if key.starts_with("DEP_") && key.ends_with(&suffix) {
+                Some((key, PathBuf::from(value)))
+            } else {
+                None
+            }
+        })
+        .collect();
+    schemas.sort();
+    schemas
+}
+
+/// Fold providers sharing a name into one, since lttng-ust only allows a provider to be
+/// defined once per tracepoint library.
+/// Classes defined identically more than once (e.g. re-exported by two dependencies) are
+/// deduplicated, classes that share a name but differ are an error, and so are common fields
+/// that differ.
+pub(in super) fn merge(providers: Vec<Provider>) -> Result<Vec<Provider>, Error> {
+    let mut merged: Vec<Provider> = Vec::new();
+    for provider in providers {
+        let existing = match merged.iter().position(|p| p.name == provider.name) {
+            Some(i) => &mut merged[i],
+            None => {
+                merged.push(provider);
+                continue;
+            }
+        };
+        // Suppression events are declared with the provider's common fields, so every class
+        // has to start with the same ones
+        if existing.common_fields != provider.common_fields {
+            return Err(Error::ConflictingCommonFields(provider.name));
+        }
+        for class in provider.classes {
+            match existing.classes.iter().find(|c| c.class_name == class.class_name) {
+                Some(c) if same_class(c, &class) => continue,
+                Some(_) => return Err(Error::ConflictingClass(
+                    format!("{}:{}", provider.name, class.class_name))),
+                None => existing.classes.push(class),
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Whether two crates define a class the same way. Schema files don't record which fields
+/// were derived, so that doesn't count.
+fn same_class(a: &EventClass, b: &EventClass) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.derived_fields = None;
+    b.derived_fields = None;
+    a == b
+}
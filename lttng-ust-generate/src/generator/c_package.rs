@@ -0,0 +1,28 @@
+use std::io;
+use std::io::prelude::*;
+
+/// A pkg-config file for C and C++ code that wants to include the public header.
+/// Everything is relative to `${pcfiledir}`, so the output directory can move.
+pub(in super) fn generate_pkg_config<F: Write>(outf: &mut F, lib_name: &str) -> io::Result<()> {
+    writeln!(outf, "prefix=${{pcfiledir}}/..")?;
+    writeln!(outf, "includedir=${{prefix}}/include")?;
+    writeln!(outf)?;
+    writeln!(outf, "Name: {}", lib_name)?;
+    writeln!(outf, "Description: lttng-ust tracepoint declarations generated by lttng-ust-generate")?;
+    writeln!(outf, "Version: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(outf, "Cflags: -I${{includedir}}")
+}
+
+/// A CMake package config defining a header-only `<lib>::<lib>` target
+pub(in super) fn generate_cmake_config<F: Write>(outf: &mut F, lib_name: &str) -> io::Result<()> {
+    writeln!(outf, "# Generated by lttng-ust-generate. Do not edit.")?;
+    writeln!(outf, "# The tracepoint functions are defined by the Rust crate that generated this file,")?;
+    writeln!(outf, "# so only the declarations are provided here.")?;
+    writeln!(outf, "get_filename_component(_{0}_prefix \"${{CMAKE_CURRENT_LIST_DIR}}/..\" ABSOLUTE)", lib_name)?;
+    writeln!(outf, "if(NOT TARGET {0}::{0})", lib_name)?;
+    writeln!(outf, "  add_library({0}::{0} INTERFACE IMPORTED)", lib_name)?;
+    writeln!(outf, "  set_target_properties({0}::{0} PROPERTIES", lib_name)?;
+    writeln!(outf, "    INTERFACE_INCLUDE_DIRECTORIES \"${{_{0}_prefix}}/include\")", lib_name)?;
+    writeln!(outf, "endif()")?;
+    writeln!(outf, "unset(_{0}_prefix)", lib_name)
+}
@@ -0,0 +1,133 @@
+use std::error;
+use std::fmt;
+use std::io;
+use bindgen::BindgenError;
+use cc;
+use schema::ParseError;
+
+/// Everything that can go wrong while generating tracepoints
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing one of the files involved in generation failed
+    Io(io::Error),
+    /// bindgen was unable to parse the generated interface header
+    Bindgen(BindgenError),
+    /// The C compiler failed to build the generated tracepoint library
+    Compile(cc::Error),
+    /// A registered schema file couldn't be read
+    Schema(ParseError),
+    /// Two aggregated crates define the same `provider:class` differently
+    ConflictingClass(String),
+    /// Two aggregated crates give the same provider (named by this) different common fields,
+    /// which its suppression events and exported schema can only have one list of
+    ConflictingCommonFields(String),
+    /// An event class (named `provider:class`) would pass lttng-ust more than the 10 arguments
+    /// `TP_ARGS` takes, counting the ones its common, optional, split, sequence and location
+    /// fields add
+    TooManyArguments(String),
+    /// No output directory was configured and `OUT_DIR` isn't set, i.e. we're not running
+    /// inside a build script
+    MissingOutDir,
+    /// A sampled or rate limited tracepoint (named `provider:instance`) was asked for with
+    /// `no_std` bindings, which have no thread-locals or clock to enforce limits with
+    LimitsNeedStd(String),
+    /// A field (named `provider:class.field`) can't be a builder method, because the builder
+    /// already has a method by that name or it isn't a valid identifier
+    UnusableSetter(String),
+    /// A `Path`, `OsStr` or serde field (named `provider:class.field`) was asked for with
+    /// `no_std` bindings, which can't name its Rust type
+    FieldNeedsStd(String),
+    /// A `Display`, `Debug` or serde field (named `provider:class.field`) was added with
+    /// `add_optional_field`
+    UnsupportedOptional(String),
+    /// An event class (named `provider:class`) with fields from `#[derive(CtfFields)]` also
+    /// has other fields, a caller location or limited instances, which the derived `emit`
+    /// method can't provide
+    DerivedClassMismatch(String),
+    /// A serde field (named `provider:class.field`) was added with `add_field` rather than
+    /// `add_serde_field`, so its Rust type is unknown
+    MissingSerdeType(String),
+    /// The `<class>_suppressed` class or `<instance>_suppressed` tracepoint generated for a
+    /// rate limited tracepoint (named `provider:name`) would take the name of an existing one
+    SuppressionNameTaken(String),
+    /// `Generator::runtime_registry` was asked for with a target (named by its triple) whose
+    /// operating system doesn't use ELF, so has no `.init_array` to register tracepoints from
+    /// at startup
+    RegistryNeedsInitArray(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error while generating tracepoints: {}", e),
+            Error::Bindgen(ref e) => write!(f, "failed to generate tracepoint bindings: {}", e),
+            Error::Compile(ref e) => write!(f, "failed to compile tracepoint library: {}", e),
+            Error::Schema(ref e) => write!(f, "failed to load schema: {}", e),
+            Error::ConflictingClass(ref name) =>
+                write!(f, "event class {} is defined differently by two crates", name),
+            Error::ConflictingCommonFields(ref name) =>
+                write!(f, "provider {} has different common fields in two crates", name),
+            Error::TooManyArguments(ref name) =>
+                write!(f, "event class {} needs more than the 10 arguments lttng-ust allows", name),
+            Error::MissingOutDir =>
+                write!(f, "OUT_DIR is not set; use Generator::out_dir outside of build scripts"),
+            Error::LimitsNeedStd(ref name) =>
+                write!(f, "tracepoint {} is sampled or rate limited, which no_std bindings can't do", name),
+            Error::UnusableSetter(ref name) =>
+                write!(f, "field {} can't be set through a builder method; rename it", name),
+            Error::FieldNeedsStd(ref name) =>
+                write!(f, "field {} has a type that no_std bindings can't take", name),
+            Error::UnsupportedOptional(ref name) =>
+                write!(f, "field {} can't be optional", name),
+            Error::DerivedClassMismatch(ref name) =>
+                write!(f, "event class {} has fields, a caller location or limits its \
+                           #[derive(CtfFields)] struct can't provide", name),
+            Error::MissingSerdeType(ref name) =>
+                write!(f, "serde field {} has no Rust type; add it with add_serde_field", name),
+            Error::SuppressionNameTaken(ref name) =>
+                write!(f, "{} is needed for the suppression events of a rate limited tracepoint", name),
+            Error::RegistryNeedsInitArray(ref target) =>
+                write!(f, "the runtime registry needs .init_array, which only ELF targets have, not {}", target),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Bindgen(ref e) => Some(e),
+            Error::Compile(ref e) => Some(e),
+            Error::Schema(ref e) => Some(e),
+            Error::ConflictingClass(_) | Error::ConflictingCommonFields(_) | Error::TooManyArguments(_) |
+            Error::MissingOutDir | Error::LimitsNeedStd(_) |
+            Error::UnusableSetter(_) | Error::FieldNeedsStd(_) | Error::UnsupportedOptional(_) |
+            Error::DerivedClassMismatch(_) | Error::MissingSerdeType(_) |
+            Error::SuppressionNameTaken(_) | Error::RegistryNeedsInitArray(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<BindgenError> for Error {
+    fn from(e: BindgenError) -> Self {
+        Error::Bindgen(e)
+    }
+}
+
+impl From<cc::Error> for Error {
+    fn from(e: cc::Error) -> Self {
+        Error::Compile(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Schema(e)
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The lttng-ust headers the generated C code depends on.
+/// If any of these change, the tracepoint library needs to be rebuilt.
+const LTTNG_HEADERS: &[&str] = &[
+    "lttng/tracepoint.h",
+    "lttng/tracepoint-event.h",
+    "lttng/ust-version.h",
+];
+
+/// A 64-bit FNV-1a hash.
+/// We can't use `DefaultHasher` here since its output isn't guaranteed to be stable
+/// between Rust releases, and fingerprints are persisted across builds.
+pub(in super) struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub(in super) fn new() -> Self {
+        Fingerprint(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(in super) fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // Separate consecutive updates, so ("ab", "c") and ("a", "bc") differ
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    pub(in super) fn to_hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// Find the lttng-ust headers the C compiler will most likely pick up.
+/// This doesn't need to be exact, it only decides what cargo watches for changes.
+pub(in super) fn lttng_headers(include_dirs: &[PathBuf], sysroot: &Option<PathBuf>) -> Vec<PathBuf> {
+    let mut search_path: Vec<PathBuf> = include_dirs.to_vec();
+    let root = sysroot.clone().unwrap_or_else(|| PathBuf::from("/"));
+    search_path.push(root.join("usr/local/include"));
+    search_path.push(root.join("usr/include"));
+
+    LTTNG_HEADERS.iter()
+        .filter_map(|header| {
+            search_path.iter()
+                .map(|dir| dir.join(header))
+                .find(|path| path.is_file())
+        })
+        .collect()
+}
+
+/// Whether the stamp file at `path` records `fingerprint`
+pub(in super) fn is_up_to_date(path: &Path, fingerprint: &Fingerprint) -> bool {
+    match fs::read_to_string(path) {
+        Ok(stamp) => stamp.trim() == fingerprint.to_hex(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,223 @@
+use std::io;
+use std::io::prelude::*;
+
+use ::{CIntegerType, CTFType, EventClass, EventInstance, Field, Provider};
+
+use super::Error;
+use super::tracepoint_interface::generate_func_name;
+
+/// `providers`, plus a `<class>_suppressed` class right after every class with rate limited
+/// instances, holding an `<instance>_suppressed` tracepoint for each of them. Like any class
+/// of the provider, it starts with the provider's common fields.
+pub(in super) fn with_suppression_events(providers: &[Provider]) -> Result<Vec<Provider>, Error> {
+    providers.iter()
+        .map(|provider| {
+            let mut with_companions = provider.clone();
+            with_companions.classes.clear();
+            for class in &provider.classes {
+                let companion = suppression_class(provider, class)?;
+                with_companions.classes.push(class.clone());
+                with_companions.classes.extend(companion);
+            }
+            Ok(with_companions)
+        })
+        .collect()
+}
+
+fn suppression_class(provider: &Provider, class: &EventClass) -> Result<Option<EventClass>, Error> {
+    let limited: Vec<&EventInstance> = class.instances.iter().filter(|i| i.rate_limit.is_some()).collect();
+    if limited.is_empty() {
+        return Ok(None);
+    }
+    let class_name = format!("{}_suppressed", class.class_name);
+    check_unused(provider, &class_name, |c| c.class_name == class_name)?;
+    let mut companion = EventClass::new(class_name);
+    companion.fields = common_fields(provider, class).to_vec();
+    companion.add_field("count", CTFType::Integer(CIntegerType::U64))
+        .describe_field("count", "Events dropped by rate limiting since the last recorded one, \
+                                  not counting those dropped by sampling");
+    for instance in limited {
+        let name = format!("{}_suppressed", instance.name);
+        check_unused(provider, &name, |c| c.instances.iter().any(|i| i.name == name))?;
+        companion.instantiate_with_level(name.clone(), instance.level)
+            .describe_instance(&name, format!("Some `{}` events were dropped", instance.name));
+    }
+    Ok(Some(companion))
+}
+
+/// Fail if a class of `provider` already uses the generated `name`
+fn check_unused<P: Fn(&EventClass) -> bool>(provider: &Provider, name: &str, uses: P) -> Result<(), Error> {
+    if provider.classes.iter().any(uses) {
+        Err(Error::SuppressionNameTaken(format!("{}:{}", provider.name, name)))
+    } else {
+        Ok(())
+    }
+}
+
+/// The common fields `class` starts with, which the suppression events of its instances
+/// record too. None if the class doesn't have them, e.g. after aggregating providers.
+fn common_fields<'a>(provider: &'a Provider, class: &EventClass) -> &'a [Field] {
+    if class.fields.starts_with(&provider.common_fields) {
+        &provider.common_fields
+    } else {
+        &[]
+    }
+}
+
+/// The C function recording the `<instance>_suppressed` event of `instance`
+fn suppressed_func_name(provider: &Provider, class: &EventClass, instance: &EventInstance) -> String {
+    let companion = EventClass::new(format!("{}_suppressed", class.class_name));
+    let companion_instance = EventInstance::new(format!("{}_suppressed", instance.name), instance.level);
+    generate_func_name(provider, &companion, &companion_instance)
+}
+
+/// Wrap `call` so that the `<instance>_suppressed` event of a rate limited `instance` is
+/// recorded first if events were dropped. `c_args` are the C arguments of `call` for each
+/// field of `class`, the common ones among them are passed on.
+pub(in super) fn with_suppressed_call(provider: &Provider, class: &EventClass, instance: &EventInstance,
+                                      c_args: &[String], call: String) -> String {
+    if instance.rate_limit.is_none() {
+        return call;
+    }
+    let mut args: Vec<&str> = c_args[..common_fields(provider, class).len()].iter().map(|a| &a[..]).collect();
+    args.push("suppressed");
+    format!("{{ if suppressed != 0 {{ unsafe {{ super::detail::{}({}) }} }} {} }}",
+            suppressed_func_name(provider, class, instance), args.join(", "), call)
+}
+
+/// The first instance that can't be generated without `std`, if any
+pub(in super) fn first_limited(providers: &[Provider]) -> Option<String> {
+    providers.iter()
+        .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+        .flat_map(|(p, c)| c.instances.iter().map(move |i| (p, i)))
+        .find(|&(_, i)| i.is_limited())
+        .map(|(p, i)| format!("{}:{}", p.name, i.name))
+}
+
+/// Statements dropping the event (by returning) when `instance` is over its limits, and
+/// binding how many events rate limiting dropped before it to `suppressed` otherwise.
+/// Sampling drops a known share of the events, so it isn't reported.
+pub(in super) fn limit_checks(instance: &EventInstance, indent: &str) -> String {
+    let mut body = String::new();
+    if let Some(every) = instance.sample_every {
+        body += &format!("thread_local! {{\n{0}    static SAMPLER: ::std::cell::Cell<u32> = ::std::cell::Cell::new(0);\n{0}}}\n\
+                          {0}if !super::limits::sample(&SAMPLER, {1}) {{\n{0}    return;\n{0}}}\n{0}",
+                         indent, every);
+    }
+    if let Some(limit) = instance.rate_limit {
+        body += &format!("static BUCKET: super::limits::TokenBucket = super::limits::TokenBucket::new({1}, {2});\n\
+                          {0}let suppressed = match BUCKET.take() {{\n\
+                          {0}    Some(dropped) => dropped,\n{0}    None => return,\n{0}}};\n{0}",
+                         indent, limit.per_second, limit.burst);
+    }
+    body
+}
+
+/// Sampling counters and token buckets. Sampling counters tell whether to keep the current
+/// event, token buckets return how many events they dropped since the last one they let
+/// through, or `None` to drop the current one.
+pub(in super) fn write_limits<F: Write>(outf: &mut F) -> io::Result<()> {
+    let module = r#"
+// Instances may only be sampled, or only rate limited
+#[allow(dead_code)]
+mod limits {
+    use ::std::cell::Cell;
+    use ::std::sync::OnceLock;
+    use ::std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+    use ::std::thread::LocalKey;
+    use ::std::time::Instant;
+
+    pub(in super) fn sample(seen: &'static LocalKey<Cell<u32>>, every: u32) -> bool {
+        seen.try_with(|seen| {
+            let dropped = seen.get();
+            if dropped + 1 >= every {
+                seen.set(0);
+                true
+            } else {
+                seen.set(dropped + 1);
+                false
+            }
+        }).unwrap_or(true)
+    }
+
+    /// When the limits module was first used, so timestamps fit in an `AtomicU64`
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+    /// Nanoseconds since `EPOCH`, never 0
+    fn now() -> u64 {
+        EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64 + 1
+    }
+
+    /// Events only take a token, with a compare-and-swap. The clock is only read once the
+    /// bucket runs empty, to add the tokens accrued since the last refill.
+    pub(in super) struct TokenBucket {
+        per_second: u32,
+        burst: u32,
+        tokens: AtomicU32,
+        /// When tokens were last added, from `now()`, or 0 until the bucket first ran empty
+        refilled: AtomicU64,
+        dropped: AtomicU64,
+    }
+
+    impl TokenBucket {
+        pub(in super) const fn new(per_second: u32, burst: u32) -> Self {
+            TokenBucket {
+                per_second, burst,
+                tokens: AtomicU32::new(burst),
+                refilled: AtomicU64::new(0),
+                dropped: AtomicU64::new(0),
+            }
+        }
+
+        pub(in super) fn take(&self) -> Option<u64> {
+            let mut tokens = self.tokens.load(Ordering::Relaxed);
+            loop {
+                if tokens == 0 {
+                    if !self.refill() {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return None;
+                    }
+                    tokens = self.tokens.load(Ordering::Relaxed);
+                    continue;
+                }
+                match self.tokens.compare_exchange_weak(tokens, tokens - 1, Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => return Some(self.dropped.swap(0, Ordering::Relaxed)),
+                    Err(current) => tokens = current,
+                }
+            }
+        }
+
+        /// Add the tokens accrued since the last refill. Whether there are tokens to take now.
+        fn refill(&self) -> bool {
+            let now = now();
+            let refilled = self.refilled.load(Ordering::Relaxed);
+            if refilled == 0 {
+                // The initial burst is spent, tokens accrue from now on
+                let _ = self.refilled.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+                return self.tokens.load(Ordering::Relaxed) != 0;
+            }
+            let elapsed = u128::from(now.saturating_sub(refilled));
+            let accrued = elapsed * u128::from(self.per_second) / 1_000_000_000;
+            if accrued == 0 {
+                // Another thread may just have refilled
+                return self.tokens.load(Ordering::Relaxed) != 0;
+            }
+            // Keep the fraction of a token accrued so far, unless the bucket is full anyway
+            let (added, at) = if accrued >= u128::from(self.burst) {
+                (self.burst, now)
+            } else {
+                (accrued as u32, refilled + (accrued * 1_000_000_000 / u128::from(self.per_second)) as u64)
+            };
+            // Only the thread moving the timestamp adds the tokens
+            if self.refilled.compare_exchange(refilled, at, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+                let burst = self.burst;
+                let _ = self.tokens.fetch_update(Ordering::Relaxed, Ordering::Relaxed,
+                                                 |tokens| Some(tokens.saturating_add(added).min(burst)));
+            }
+            true
+        }
+    }
+}
+"#;
+    write!(outf, "{}", module)
+}
@@ -1,16 +1,33 @@
 use bindgen::Builder;
 use cc;
 use std::env;
-use std::path::PathBuf;
-use super::{CTFType, Provider};
+use std::fs;
+use std::path::{Path, PathBuf};
+use super::{CTFType, LogLevel, Provider};
+use schema;
 
+mod aggregate;
+mod c_package;
+mod error;
+mod incremental;
+mod limits;
 mod tracepoint_impl;
 mod tracepoint_interface;
+mod redaction;
 mod rust_bindings;
+mod sources;
+mod toolchain;
+
+pub use self::error::Error;
+pub use self::sources::GeneratedSources;
+pub use self::redaction::RedactionPolicy;
+pub use self::toolchain::LinkMode;
 
 use self::tracepoint_impl::{generate_tp_impl, generate_tp_header};
 use self::tracepoint_interface::{generate_interface_impl, generate_interface_header, whitelist_interface};
-use self::rust_bindings::{generate_rust_bindings};
+use self::tracepoint_interface::UstAbi;
+use self::rust_bindings::{generate_rust_bindings, BindingOptions};
+use self::c_package::{generate_cmake_config, generate_pkg_config};
 
 /// Encapsulates the logic for generating the C and Rust source files needed to realize your
 /// tracepoints
@@ -18,15 +35,50 @@ pub struct Generator {
     lib_name: String,
     providers: Vec<Provider>,
     output_file_name: PathBuf,
+    out_dir: Option<PathBuf>,
+    target: Option<String>,
+    sysroot: Option<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, Option<String>)>,
+    c_flags: Vec<String>,
+    clang_args: Vec<String>,
+    link_mode: Option<LinkMode>,
+    lttng_ust_lib_dirs: Vec<PathBuf>,
+    schema_files: Vec<PathBuf>,
+    imported_schema_files: Vec<PathBuf>,
+    export: bool,
+    no_std: bool,
+    max_level: Option<LogLevel>,
+    builders: bool,
+    redaction_policy: RedactionPolicy,
+    runtime_registry: bool,
+    rerun_directives: bool,
 }
 
 impl Default for Generator {
     fn default() -> Self {
-        let generate_path = PathBuf::from(env::var("OUT_DIR").unwrap());
         Self {
             lib_name: "tracepoints".into(),
             providers: Vec::new(),
-            output_file_name: generate_path.join("tracepoints.rs"),
+            output_file_name: PathBuf::from("tracepoints.rs"),
+            out_dir: None,
+            target: None,
+            sysroot: None,
+            include_dirs: Vec::new(),
+            defines: Vec::new(),
+            c_flags: Vec::new(),
+            clang_args: Vec::new(),
+            link_mode: None,
+            lttng_ust_lib_dirs: Vec::new(),
+            schema_files: Vec::new(),
+            imported_schema_files: Vec::new(),
+            export: false,
+            no_std: false,
+            max_level: None,
+            builders: false,
+            redaction_policy: RedactionPolicy::Verbatim,
+            runtime_registry: false,
+            rerun_directives: true,
         }
     }
 }
@@ -44,86 +96,681 @@ impl Generator {
         self
     }
 
+    /// Add every provider described by a [schema file](::schema).
+    /// The file is read when generating, and cargo is told to rerun the build script
+    /// whenever it changes.
+    pub fn register_schema_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.schema_files.push(path.into());
+        self
+    }
+
+    /// Publish this generator's providers to dependent crates instead of building a tracepoint
+    /// library for them.
+    ///
+    /// This is how library crates in a workspace contribute tracepoints to a single library
+    /// built by the final binary. The library crate still gets its Rust bindings, but the
+    /// C functions behind them are compiled by whichever crate calls
+    /// [`import_dependency_providers`](::Generator::import_dependency_providers).
+    /// The providers are published through `cargo:` metadata, so the exporting crate's
+    /// `Cargo.toml` needs a `links` key.
+    pub fn export_providers(mut self) -> Self {
+        self.export = true;
+        self
+    }
+
+    /// Include the providers exported by every direct dependency (see
+    /// [`export_providers`](::Generator::export_providers)) in the generated tracepoint library.
+    ///
+    /// Imported providers only get C code: the exporting crates have Rust bindings of their own.
+    /// Combined with `export_providers`, this re-exports them, which allows aggregating
+    /// providers from crates the final binary doesn't depend on directly.
+    /// Crates may define the same provider, as long as they give it the same common fields and
+    /// agree on any class they both define.
+    pub fn import_dependency_providers(mut self) -> Self {
+        for (key, path) in aggregate::dependency_schemas() {
+            println!("cargo:rerun-if-env-changed={}", key);
+            self.imported_schema_files.push(path);
+        }
+        self
+    }
+
+    /// Only refer to `core` in the generated Rust bindings, so tracepoints can be fired from
+    /// `#![no_std]` crates. Tracepoints taking strings then expect a `&core::ffi::CStr`,
+    /// which requires Rust 1.64 or later.
+    ///
+    /// The generated code names `::core` directly, which 2015 edition crates can only do
+    /// after `extern crate core;`. Sampled and rate limited tracepoints, as well as `Path`,
+    /// `OsStr` and serde fields, aren't supported and make generation fail.
+    /// Defaults to `false`.
+    pub fn no_std(mut self, enable: bool) -> Self {
+        self.no_std = enable;
+        self
+    }
+
+    /// Compile out every Rust tracepoint less severe than `level`, the way the `log` crate's
+    /// `max_level_*` features do.
+    /// The Rust functions for those tracepoints keep their signature but have empty,
+    /// `#[inline(always)]` bodies, so they vanish from the binary entirely. The C functions
+    /// are still built, so C callers are unaffected.
+    ///
+    /// To drive this from cargo features, declare features on your crate and check them in
+    /// your build script:
+    ///
+    /// ```no_run
+    /// # use lttng_ust_generate::{Generator, LogLevel};
+    /// let mut generator = Generator::default();
+    /// if std::env::var_os("CARGO_FEATURE_RELEASE_TRACING").is_some() {
+    ///     generator = generator.max_level(LogLevel::Info);
+    /// }
+    /// ```
+    pub fn max_level(mut self, level: LogLevel) -> Self {
+        self.max_level = Some(level);
+        self
+    }
+
+    /// Generate a typed builder for every tracepoint instead of a function taking positional
+    /// arguments. Each builder has a setter named after every field, and `emit` only becomes
+    /// available once all of them have been called:
+    ///
+    /// ```ignore
+    /// let event = tracepoints::my_provider::my_event();
+    /// if event.enabled() {
+    ///     event.request_id(7).path("/index.html").emit();
+    /// }
+    /// ```
+    ///
+    /// The builders are zero-cost: until fields are set, they are zero-sized, and everything
+    /// is inlined into a single call to the C tracepoint function. Defaults to `false`.
+    ///
+    /// Fields named after Rust keywords get raw identifier setters (`.r#type(3)`), and fields
+    /// named `emit` or `enabled` make generation fail with
+    /// [`Error::UnusableSetter`](::Error::UnusableSetter).
+    pub fn builders(mut self, enable: bool) -> Self {
+        self.builders = enable;
+        self
+    }
+
+    /// Decide what the tracepoint library records for fields marked with
+    /// [`EventClass::mark_sensitive`](::EventClass::mark_sensitive). Call sites don't change,
+    /// so this is typically driven by a cargo feature of your crate:
+    ///
+    /// ```no_run
+    /// # use lttng_ust_generate::{Generator, RedactionPolicy};
+    /// let mut generator = Generator::default();
+    /// if std::env::var_os("CARGO_FEATURE_PRODUCTION").is_some() {
+    ///     generator = generator.redaction_policy(RedactionPolicy::NoWrite);
+    /// }
+    /// ```
+    ///
+    /// Applies to imported providers as well. Defaults to
+    /// [`RedactionPolicy::Verbatim`](::RedactionPolicy::Verbatim).
+    pub fn redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policy = policy;
+        self
+    }
+
+    /// Make the generated bindings register a descriptor of each of their tracepoints with
+    /// `lttng_ust::registry` when the program starts, so it can list them and check whether
+    /// they are enabled at runtime (e.g. for a `--list-tracepoints` flag).
+    ///
+    /// The bindings then refer to the `lttng_ust` crate, which has to be a dependency.
+    /// Registration relies on ELF `.init_array` sections, so generating for a target whose
+    /// operating system doesn't use ELF (e.g. Windows or macOS) fails with
+    /// [`Error::RegistryNeedsInitArray`](::Error::RegistryNeedsInitArray).
+    /// Defaults to `false`.
+    pub fn runtime_registry(mut self, enable: bool) -> Self {
+        self.runtime_registry = enable;
+        self
+    }
+
     /// Sets the name of the root Rust source file into which tracepoint bindings
     /// are generated.
+    /// Relative paths are relative to the [output directory](::Generator::out_dir).
+    /// Defaults to `tracepoints.rs`.
     pub fn output_file_name<P: Into<PathBuf>>(mut self, p: P) -> Self {
         self.output_file_name = p.into();
         self
     }
 
+    /// Sets the directory all generated sources are written to.
+    /// Defaults to the `OUT_DIR` cargo passes to build scripts.
+    pub fn out_dir<P: Into<PathBuf>>(mut self, p: P) -> Self {
+        self.out_dir = Some(p.into());
+        self
+    }
+
+    /// Overrides the target triple the tracepoint library is built for.
+    /// Defaults to the `TARGET` cargo passes to build scripts.
+    pub fn target<S: Into<String>>(mut self, triple: S) -> Self {
+        self.target = Some(triple.into());
+        self
+    }
+
+    /// Sets the sysroot used by both the C compiler and libclang.
+    /// When cross compiling without an explicit sysroot, we ask the target C compiler for its
+    /// built-in sysroot (`-print-sysroot`) so bindgen sees the same headers it does.
+    pub fn sysroot<P: Into<PathBuf>>(mut self, p: P) -> Self {
+        self.sysroot = Some(p.into());
+        self
+    }
+
+    /// Adds a directory to the header search path of both the C compiler and libclang.
+    /// Useful when the lttng-ust headers aren't installed in a default location.
+    pub fn include_dir<P: Into<PathBuf>>(mut self, p: P) -> Self {
+        self.include_dirs.push(p.into());
+        self
+    }
+
+    /// Defines a preprocessor macro for both the C compiler and libclang.
+    pub fn define<K: Into<String>>(mut self, key: K, value: Option<&str>) -> Self {
+        self.defines.push((key.into(), value.map(String::from)));
+        self
+    }
+
+    /// Passes an extra flag to the C compiler only.
+    pub fn c_flag<S: Into<String>>(mut self, flag: S) -> Self {
+        self.c_flags.push(flag.into());
+        self
+    }
+
+    /// Passes an extra argument to libclang only (i.e. when generating the raw bindings).
+    pub fn clang_arg<S: Into<String>>(mut self, arg: S) -> Self {
+        self.clang_args.push(arg.into());
+        self
+    }
+
+    /// Chooses whether `liblttng-ust` is linked statically or dynamically.
+    ///
+    /// `lttng-ust-sys` (used by the `tracef` and `context` features of `lttng-ust`) links it
+    /// on its own, statically if the `LTTNG_UST_STATIC` environment variable is set to
+    /// anything but `0`. By default the tracepoint library follows it: it reads the
+    /// `DEP_LTTNG_UST_STATIC` metadata `lttng-ust-sys` publishes when it is a direct
+    /// dependency, or else `LTTNG_UST_STATIC` itself. Only override that when nothing links
+    /// `lttng-ust-sys`, or the two will disagree.
+    pub fn lttng_ust_link_mode(mut self, mode: LinkMode) -> Self {
+        self.link_mode = Some(mode);
+        self
+    }
+
+    /// Adds a directory the linker should search for `liblttng-ust`.
+    pub fn lttng_ust_lib_dir<P: Into<PathBuf>>(mut self, p: P) -> Self {
+        self.lttng_ust_lib_dirs.push(p.into());
+        self
+    }
+
+    /// Controls whether [`generate`](::Generator::generate) prints `cargo:rerun-if-changed`
+    /// directives for registered schema files and the lttng-ust headers. Defaults to `true`.
+    ///
+    /// Note that once a build script prints any `rerun-if-changed` directive cargo stops
+    /// rerunning it whenever any file in the package changes. If your build script depends
+    /// on other files, either print directives for them too or disable this.
+    pub fn emit_rerun_directives(mut self, enable: bool) -> Self {
+        self.rerun_directives = enable;
+        self
+    }
+
     /// Perform generation.
-    pub fn generate(self) -> Result<(), ()> {
-        // TODO: replace expects with something that passes errors upward
-        let mut generate_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-        generate_path.push("lttng-tracepoints");
-        generate_path.push(&self.lib_name);
+    /// This is intended to be called from a build script: on top of writing out all the
+    /// generated sources it compiles the C half of the tracepoints and tells cargo how to
+    /// link everything.
+    ///
+    /// Generation is skipped entirely when neither the providers, the generator settings nor
+    /// the lttng-ust headers changed since the last successful run.
+    pub fn generate(self) -> Result<(), Error> {
+        let out_dir = self.resolve_out_dir()?;
+        let generate_path = out_dir.join(self.source_dir());
 
-        let mut builder = Builder::default();
-        builder = builder.header(self.interface_header(&generate_path).to_string_lossy());
+        let target = toolchain::target_triple(&self.target);
+        let sysroot = self.resolve_sysroot(&target);
+        let providers = self.all_providers()?;
+        let c_providers = self.c_providers(&providers)?;
 
-        // Generate C modules
-        self.generate_c_sources(&generate_path);
-        builder = whitelist_interface(&self.providers, builder);
+        let headers = incremental::lttng_headers(&self.include_dirs, &sysroot);
+        if self.rerun_directives {
+            let watched = self.schema_files.iter().chain(&self.imported_schema_files).chain(&headers);
+            for path in watched {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+
+        let fingerprint = self.fingerprint(&providers, &c_providers, &target, &sysroot, &headers)?;
+        let stamp = generate_path.join(self.local_name(".stamp"));
+        if !(incremental::is_up_to_date(&stamp, &fingerprint) && self.outputs_exist(&out_dir)) {
+            // Don't trust a stale stamp if we fail part way through
+            if stamp.exists() {
+                fs::remove_file(&stamp)?;
+            }
+            self.generate_sources_with(&providers, &c_providers, &target, &sysroot, Some(&out_dir))?
+                .write_to(&out_dir)?;
+            if !self.export {
+                self.compile(&target, &sysroot, &generate_path, &out_dir)?;
+            }
+            fs::write(&stamp, fingerprint.to_hex())?;
+        }
+
+        if self.export {
+            println!("cargo:{}={}", aggregate::SCHEMA_METADATA_KEY,
+                     generate_path.join(self.local_name(".schema")).display());
+        } else {
+            self.emit_link_directives(&out_dir);
+            self.emit_c_package_metadata(&generate_path);
+        }
+
+        Ok(())
+    }
+
+    /// Generate all C, header and Rust sources in memory, without compiling anything.
+    /// Unlike [`generate`](::Generator::generate), this works outside of build scripts, which
+    /// makes it possible to vendor pre-generated sources. See
+    /// [`compile_pregenerated`](::Generator::compile_pregenerated) for the other half.
+    pub fn generate_sources(&self) -> Result<GeneratedSources, Error> {
+        let out_dir = self.out_dir.clone().or_else(|| env::var_os("OUT_DIR").map(PathBuf::from));
+        self.generate_sources_into(out_dir.as_deref())
+    }
+
+    /// Generate all C, header and Rust sources into `dir`, without compiling anything.
+    /// The Rust sources only refer to each other by relative path (unless an absolute
+    /// [`output_file_name`](::Generator::output_file_name) was set), so `dir` may be
+    /// checked in and moved around freely.
+    pub fn write_sources<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        self.generate_sources_into(Some(dir.as_ref()))?.write_to(dir)?;
+        Ok(())
+    }
+
+    fn generate_sources_into(&self, out_dir: Option<&Path>) -> Result<GeneratedSources, Error> {
+        let target = toolchain::target_triple(&self.target);
+        let sysroot = self.resolve_sysroot(&target);
+        let providers = self.all_providers()?;
+        let c_providers = self.c_providers(&providers)?;
+        self.generate_sources_with(&providers, &c_providers, &target, &sysroot, out_dir)
+    }
+
+    /// Compile sources previously written by [`write_sources`](::Generator::write_sources)
+    /// and emit the linker directives cargo needs. Only the library name and toolchain
+    /// settings of this generator are used, so build scripts using this don't need
+    /// libclang.
+    pub fn compile_pregenerated<P: AsRef<Path>>(self, dir: P) -> Result<(), Error> {
+        let generate_path = dir.as_ref().join(self.source_dir());
+        let target = toolchain::target_triple(&self.target);
+        let sysroot = self.resolve_sysroot(&target);
+
+        if self.rerun_directives {
+            println!("cargo:rerun-if-changed={}", generate_path.display());
+        }
+        let out_dir = self.resolve_out_dir()?;
+        self.compile(&target, &sysroot, &generate_path, &out_dir)?;
+        self.emit_link_directives(&out_dir);
+        self.emit_c_package_metadata(&generate_path);
+
+        Ok(())
+    }
+
+    /// `providers` get Rust bindings, `c_providers` get compiled into the tracepoint library.
+    /// The latter is empty when exporting.
+    fn generate_sources_with(&self,
+                             providers: &[Provider],
+                             c_providers: &[Provider],
+                             target: &Option<String>,
+                             sysroot: &Option<PathBuf>,
+                             out_dir: Option<&Path>) -> Result<GeneratedSources, Error> {
+        if self.no_std {
+            if let Some(name) = limits::first_limited(providers) {
+                return Err(Error::LimitsNeedStd(name));
+            }
+            if let Some(name) = rust_bindings::first_std_only_field(providers) {
+                return Err(Error::FieldNeedsStd(name));
+            }
+        }
+        let mismatched = providers.iter()
+            .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+            .find(|&(_, c)| !c.matches_derived_fields());
+        if let Some((provider, class)) = mismatched {
+            return Err(Error::DerivedClassMismatch(format!("{}:{}", provider.name, class.class_name)));
+        }
+        if let Some(name) = rust_bindings::first_untyped_serde_field(providers) {
+            return Err(Error::MissingSerdeType(name));
+        }
+        if let Some(name) = rust_bindings::first_unsupported_optional(providers) {
+            return Err(Error::UnsupportedOptional(name));
+        }
+        if self.runtime_registry {
+            if let Some(ref target) = *target {
+                if !toolchain::has_init_array(target) {
+                    return Err(Error::RegistryNeedsInitArray(target.clone()));
+                }
+            }
+        }
+        if self.builders {
+            if let Some(name) = rust_bindings::first_unusable_setter(providers) {
+                return Err(Error::UnusableSetter(name));
+            }
+        }
+        // Exports leave the suppression events to whoever compiles the tracepoints
+        let exported_providers = providers;
+        let providers = &limits::with_suppression_events(providers)?;
+        let c_providers = &limits::with_suppression_events(c_providers)?;
+        let overlong = tracepoint_impl::first_overlong_class(providers)
+            .or_else(|| tracepoint_impl::first_overlong_class(c_providers));
+        if let Some(name) = overlong {
+            return Err(Error::TooManyArguments(name));
+        }
+        let source_dir = self.source_dir();
+        let tp_hdr_name = self.local_name("_tps.h");
+        let in_hdr_name = self.local_name(".h");
+        let mut sources = GeneratedSources::new();
 
-        // Parse C modules and generate unsafe Rust bindings for the interface
-        let bindings_file = generate_path.join("tracepoints.rs");
-        builder
-            .generate()
-            .expect(&format!("Failed to generate tracepoint bindings for {}", self.lib_name))
-            .write_to_file(&bindings_file)
-            .expect(&format!("Failed to write raw tracepoint bindings for {}", self.lib_name));
+        // Parse the interface and generate unsafe Rust bindings for it. The tracepoint header
+        // declares the lttng-ust tracepoint structs, which the bindings read the state of. Their
+        // names depend on the lttng-ust version, so without its headers the bindings ask C.
+        let headers = incremental::lttng_headers(&self.include_dirs, sysroot);
+        let abi = toolchain::lttng_ust_version(&headers).map(UstAbi::from_version);
+        let mut in_header = Vec::new();
+        generate_interface_header(&mut in_header, providers, &self.lib_name)?;
+        let mut builder = Builder::default()
+            .header_contents(&in_hdr_name, &String::from_utf8_lossy(&in_header))
+            .clang_args(self.bindgen_args(target, sysroot));
+        if abi.is_some() {
+            let mut declared_tps = Vec::new();
+            generate_tp_header(&mut declared_tps, providers, &tp_hdr_name, self.redaction_policy)?;
+            builder = builder.header_contents(&tp_hdr_name, &String::from_utf8_lossy(&declared_tps));
+        }
+        if self.no_std {
+            builder = builder.use_core().ctypes_prefix("::core::ffi");
+        }
+        builder = whitelist_interface(providers, abi, builder);
+        let raw_bindings = builder.generate()?.to_string();
+        sources.add(source_dir.join("tracepoints.rs"), raw_bindings.into_bytes());
 
         // Generate pretty rust module
-        generate_rust_bindings(&self.output_file_name, &self.providers, &bindings_file)
-            .expect("Failed to generate rust sources");
+        let mut bindings = Vec::new();
+        // `is_none_or` would need Rust 1.82 to build the generator itself
+        #[allow(clippy::unnecessary_map_or)]
+        let unsafe_attributes = toolchain::rustc_version().map_or(true, |version| version >= (1, 82));
+        let options = BindingOptions {
+            no_std: self.no_std,
+            max_level: self.max_level,
+            builders: self.builders,
+            runtime_registry: self.runtime_registry,
+            unsafe_attributes,
+            abi,
+        };
+        generate_rust_bindings(&mut bindings, providers, &self.raw_bindings_include(out_dir)?, &options)?;
+        sources.add(self.output_file_name.clone(), bindings);
+
+        if self.export {
+            let mut exported = Vec::new();
+            schema::write(&mut exported, exported_providers)?;
+            sources.add(source_dir.join(self.local_name(".schema")), exported);
+            return Ok(sources);
+        }
+
+        // Generate C modules
+        let mut tp_header = Vec::new();
+        generate_tp_header(&mut tp_header, c_providers, &tp_hdr_name, self.redaction_policy)?;
+        sources.add(source_dir.join(&tp_hdr_name), tp_header);
+
+        let mut tp_impl = Vec::new();
+        generate_tp_impl(&mut tp_impl, &tp_hdr_name)?;
+        sources.add(source_dir.join(self.local_name("_tp_impl.c")), tp_impl);
+
+        let mut in_impl = Vec::new();
+        generate_interface_impl(&mut in_impl, c_providers, &in_hdr_name, &tp_hdr_name, self.redaction_policy)?;
+        sources.add(source_dir.join(self.local_name("_interface.c")), in_impl);
+
+        let mut public_header = Vec::new();
+        generate_interface_header(&mut public_header, c_providers, &self.lib_name)?;
+        sources.add(source_dir.join("include").join(&in_hdr_name), public_header);
+
+        // Make the public header easy to find from C and C++ build systems
+        let mut pkg_config = Vec::new();
+        generate_pkg_config(&mut pkg_config, &self.lib_name)?;
+        sources.add(source_dir.join("pkgconfig").join(self.local_name(".pc")), pkg_config);
+        let mut cmake_config = Vec::new();
+        generate_cmake_config(&mut cmake_config, &self.lib_name)?;
+        sources.add(source_dir.join("cmake").join(self.local_name("-config.cmake")), cmake_config);
+
+        Ok(sources)
+    }
+
+    fn compile(&self, target: &Option<String>,
+               sysroot: &Option<PathBuf>,
+               generate_path: &Path,
+               lib_dir: &Path) -> Result<(), Error> {
+        let mut build = self.c_build(target);
+        if let Some(sysroot) = sysroot {
+            build.flag(format!("--sysroot={}", sysroot.display()));
+        }
+        // Keep the location of the output directory out of the debug info
+        let prefix_map = ["-ffile-prefix-map", "-fdebug-prefix-map"].iter()
+            .map(|flag| format!("{}={}=.", flag, lib_dir.display()))
+            .find(|flag| build.is_flag_supported(flag).unwrap_or(false));
+        if let Some(flag) = prefix_map {
+            build.flag(flag);
+        }
+        build
+            // We tell cargo how to link the library ourselves, see emit_link_directives
+            .cargo_metadata(false)
+            .out_dir(lib_dir)
+            .include(generate_path)
+            .include(generate_path.join("include"))
+            .file(generate_path.join(self.local_name("_tp_impl.c")))
+            .file(generate_path.join(self.local_name("_interface.c")))
+            .try_compile(&self.lib_name)?;
+        Ok(())
+    }
+
+    /// Where the public C header declaring every tracepoint function ends up.
+    ///
+    /// C and C++ code compiled into the same binary can include `<lib name>.h` from here to
+    /// fire the same tracepoints as the Rust side. The functions are defined by the tracepoint
+    /// library this generator builds, so that code must not link its own copy.
+    /// The same directory is also exported to dependent build scripts as `DEP_<links>_INCLUDE`,
+    /// next to `DEP_<links>_PKGCONFIG` and `DEP_<links>_CMAKE`, which point at a pkg-config file
+    /// and a CMake package config respectively.
+    pub fn c_include_dir(&self) -> Result<PathBuf, Error> {
+        Ok(self.resolve_out_dir()?.join(self.source_dir()).join("include"))
+    }
 
+    fn emit_c_package_metadata(&self, generate_path: &Path) {
+        println!("cargo:include={}", generate_path.join("include").display());
+        println!("cargo:pkgconfig={}", generate_path.join("pkgconfig").display());
+        println!("cargo:cmake={}", generate_path.join("cmake").display());
+    }
+
+    fn emit_link_directives(&self, lib_dir: &Path) {
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        // rustc puts our native libraries before the upstream crates on the linker command
+        // line, so nothing would pull in the functions only they call. That's the case for
+        // imported providers and for `#[derive(CtfFields)]` structs defined in other crates.
+        println!("cargo:rustc-link-lib=static:+whole-archive={}", self.lib_name);
         // Note: this MUST be after all tracepoints are generated so that the linker
         // doesn't get confused. The static C library generated by cc-rs needs to precede
         // lttng-ust in the linker command line.
-        println!("cargo:rustc-link-lib=lttng-ust");
+        for dir in &self.lttng_ust_lib_dirs {
+            println!("cargo:rustc-link-search=native={}", dir.display());
+        }
+        let link_mode = match self.link_mode {
+            Some(mode) => mode,
+            None => {
+                println!("cargo:rerun-if-env-changed=LTTNG_UST_STATIC");
+                toolchain::lttng_ust_sys_link_mode()
+            }
+        };
+        println!("cargo:rustc-link-lib={}=lttng-ust", link_mode.cargo_kind());
+        if link_mode == LinkMode::Static {
+            // The probe registration code emitted by TRACEPOINT_DEFINE uses dlopen
+            println!("cargo:rustc-link-lib=dl");
+        }
+    }
 
-        Ok(())
+    fn all_providers(&self) -> Result<Vec<Provider>, Error> {
+        let mut providers = self.providers.clone();
+        for path in &self.schema_files {
+            providers.extend(schema::parse_file(path)?);
+        }
+        Ok(providers)
     }
 
-    fn generate_c_sources(&self, generate_path: &PathBuf) {
-        use std::fs;
-        // Make sure the output directory exists
-        fs::create_dir_all(generate_path)
-            .expect("Failed to create source directory");
+    /// Everything that goes into the tracepoint library: our own providers plus any imported
+    /// ones, or nothing at all if we're only exporting
+    fn c_providers(&self, providers: &[Provider]) -> Result<Vec<Provider>, Error> {
+        if self.export {
+            return Ok(Vec::new());
+        }
+        let mut c_providers = providers.to_vec();
+        for path in &self.imported_schema_files {
+            c_providers.extend(schema::parse_file(path)?);
+        }
+        aggregate::merge(c_providers)
+    }
+
+    /// Everything that influences the generated sources or the compiled library
+    fn fingerprint(&self, providers: &[Provider],
+                   c_providers: &[Provider],
+                   target: &Option<String>,
+                   sysroot: &Option<PathBuf>,
+                   headers: &[PathBuf]) -> Result<incremental::Fingerprint, Error> {
+        let mut fingerprint = incremental::Fingerprint::new();
+        fingerprint.update(env!("CARGO_PKG_VERSION").as_bytes());
+        // Spelled out without `..`, so new settings have to be sorted into one of the two groups
+        let Generator {
+            ref lib_name, ref output_file_name, export, no_std, max_level, builders,
+            redaction_policy, runtime_registry, ref include_dirs, ref defines, ref c_flags,
+            ref clang_args,
+            // Resolved into `target` and `sysroot`
+            target: _, sysroot: _,
+            // Already part of `providers` and `c_providers`
+            providers: _, schema_files: _, imported_schema_files: _,
+            // Only decide where outputs go, or what is printed for cargo on every run
+            out_dir: _, link_mode: _, lttng_ust_lib_dirs: _, rerun_directives: _,
+        } = *self;
+        let settings = format!("{:?}", (
+            lib_name, output_file_name, export, target, sysroot,
+            (no_std, max_level, builders, redaction_policy, runtime_registry),
+            include_dirs, defines, c_flags, clang_args,
+            // Decides how the registry is spelled
+            toolchain::rustc_version(),
+        ));
+        fingerprint.update(settings.as_bytes());
 
-        // Generate and build C-language files
-        let tp_hdr_pth = &self.tracepoint_header(&generate_path);
-        let in_hdr_pth = &self.interface_header(&generate_path);
-        generate_tp_header(tp_hdr_pth, &self.providers)
-            .expect("Failed to generate tracepoint header");
-        generate_interface_header(in_hdr_pth, &self.providers)
-            .expect("Failed to generate interface header");
+        let mut schema_text = Vec::new();
+        schema::write(&mut schema_text, providers)?;
+        fingerprint.update(&schema_text);
+        schema_text.clear();
+        schema::write(&mut schema_text, c_providers)?;
+        fingerprint.update(&schema_text);
 
-        let impl_paths = [
-            self.local_path(generate_path, "_tp_impl.c"),
-            self.local_path(generate_path, "_interface.c"),
+        for header in headers {
+            fingerprint.update(header.to_string_lossy().as_bytes());
+            fingerprint.update(&fs::read(header)?);
+        }
+        Ok(fingerprint)
+    }
+
+    /// Whether everything a previous `generate` produced is still around
+    fn outputs_exist(&self, out_dir: &Path) -> bool {
+        let outputs = [
+            out_dir.join(&self.output_file_name),
+            out_dir.join(self.source_dir()).join("tracepoints.rs"),
+            if self.export {
+                out_dir.join(self.source_dir()).join(self.local_name(".schema"))
+            } else {
+                out_dir.join(format!("lib{}.a", self.lib_name))
+            },
         ];
-        generate_tp_impl(&impl_paths[0], tp_hdr_pth)
-            .expect("Failed to generate tracepoint implementation");
-        generate_interface_impl(&impl_paths[1], &self.providers, tp_hdr_pth, in_hdr_pth)
-            .expect("Failed to generate interface implementation");
+        outputs.iter().all(|path| path.is_file())
+    }
 
-        cc::Build::new()
-            .files(&impl_paths)
-            .include(generate_path)
-            .compile(&self.lib_name);
+    fn resolve_out_dir(&self) -> Result<PathBuf, Error> {
+        self.out_dir.clone()
+            .or_else(|| env::var_os("OUT_DIR").map(PathBuf::from))
+            .ok_or(Error::MissingOutDir)
     }
 
-    fn tracepoint_header(&self, generate_path: &PathBuf) -> PathBuf {
-        self.local_path(generate_path, "_tps.h")
+    fn resolve_sysroot(&self, target: &Option<String>) -> Option<PathBuf> {
+        self.sysroot.clone().or_else(|| {
+            if toolchain::is_cross_compiling(target) {
+                self.c_build(target).try_get_compiler().ok()
+                    .and_then(|compiler| toolchain::probe_sysroot(&compiler))
+            } else {
+                None
+            }
+        })
     }
 
-    fn interface_header(&self, generate_path: &PathBuf) -> PathBuf {
-        self.local_path(generate_path, "_int.h")
+    /// The base C compiler configuration, shared between compilation and sysroot probing
+    fn c_build(&self, target: &Option<String>) -> cc::Build {
+        let mut build = cc::Build::new();
+        if let Some(target) = target {
+            build.target(target);
+        }
+        for dir in &self.include_dirs {
+            build.include(dir);
+        }
+        for (key, value) in &self.defines {
+            build.define(key, value.as_ref().map(|v| &v[..]));
+        }
+        for flag in &self.c_flags {
+            build.flag(flag);
+        }
+        build
     }
 
-    fn local_path(&self, generate_path: &PathBuf, suffix: &str) -> PathBuf {
-        generate_path.join(format!("{}{}", self.lib_name, suffix))
+    fn bindgen_args(&self, target: &Option<String>, sysroot: &Option<PathBuf>) -> Vec<String> {
+        let mut args = Vec::new();
+        // bindgen picks up TARGET on its own, but not an explicit override, so always be explicit
+        if let Some(ref target) = *target {
+            args.push(format!("--target={}", toolchain::clang_target(target)));
+        }
+        if let Some(ref sysroot) = *sysroot {
+            args.push(format!("--sysroot={}", sysroot.display()));
+        }
+        for dir in &self.include_dirs {
+            args.push(format!("-I{}", dir.display()));
+        }
+        for (key, value) in &self.defines {
+            match value {
+                Some(value) => args.push(format!("-D{}={}", key, value)),
+                None => args.push(format!("-D{}", key)),
+            }
+        }
+        args.extend(self.clang_args.iter().cloned());
+        args
+    }
+
+    /// Where the raw bindings live, as seen from the root Rust source file.
+    /// This is the argument to an `include!`, and never depends on where the output directory
+    /// is unless it has to: cached or reproducible builds may compile it from anywhere.
+    fn raw_bindings_include(&self, out_dir: Option<&Path>) -> Result<String, Error> {
+        let raw_bindings = self.source_dir().join("tracepoints.rs");
+        let cargo_out_dir = env::var_os("OUT_DIR").map(PathBuf::from);
+        if out_dir.is_some() && out_dir == cargo_out_dir.as_deref() {
+            return Ok(format!("concat!(env!(\"OUT_DIR\"), \"/{}\")", raw_bindings.display()));
+        }
+
+        if self.output_file_name.is_absolute() {
+            let out_dir = out_dir.ok_or(Error::MissingOutDir)?;
+            return Ok(format!("{:?}", out_dir.join(raw_bindings).display().to_string()));
+        }
+
+        // include! resolves paths relative to the file containing it
+        let depth = self.output_file_name.parent().map_or(0, |p| p.components().count());
+        let mut include = PathBuf::new();
+        for _ in 0..depth {
+            include.push("..");
+        }
+        include.push(raw_bindings);
+        Ok(format!("{:?}", include.display().to_string()))
+    }
+
+    /// The directory (relative to the output directory) holding everything but the root
+    /// Rust source file
+    fn source_dir(&self) -> PathBuf {
+        Path::new("lttng-tracepoints").join(&self.lib_name)
+    }
+
+    fn local_name(&self, suffix: &str) -> String {
+        format!("{}{}", self.lib_name, suffix)
     }
 }
 
@@ -140,9 +787,537 @@ fn ctf_field_c_type(ty: CTFType) -> &'static str {
 
         CTFType::String | CTFType::StringNoWrite => "const char *",
         CTFType::Array(i, _) | CTFType::ArrayNoWrite(i, _) => i.c_pointer_type(),
-        CTFType::ArrayText(_) => "const char *",
+        CTFType::ArrayText(_) | CTFType::ArrayTextNoWrite(_) => "const char *",
         CTFType::Sequence(i) | CTFType::SequenceNoWrite(i) => i.c_pointer_type(),
-        CTFType::SequenceText | CTFType::SequenceTextNoWrite => "const char *",
+        CTFType::SequenceText | CTFType::SequenceTextNoWrite |
+        CTFType::Display | CTFType::Debug => "const char *",
         CTFType::Enum | CTFType::EnumNoWrite => unimplemented!(),
+        ty => unreachable!("{:?} is lowered by EventClass::c_fields", ty),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {CIntegerType, EventClass, FieldGroup, LogLevel, SerdeEncoding};
+
+    const BASE: &str = "
+provider p
+    common id integer u64
+    class c
+        field f integer u32
+        instance i
+";
+
+    fn fingerprint_of(generator: &Generator) -> String {
+        fingerprint_with(generator, &None, &None)
+    }
+
+    fn fingerprint_with(generator: &Generator, target: &Option<String>, sysroot: &Option<PathBuf>) -> String {
+        let providers = generator.all_providers().unwrap();
+        let c_providers = generator.c_providers(&providers).unwrap();
+        generator.fingerprint(&providers, &c_providers, target, sysroot, &[]).unwrap().to_hex()
+    }
+
+    fn schema_generator(schema_text: &str) -> Generator {
+        Generator { providers: schema::parse(schema_text).unwrap(), ..Generator::default() }
+    }
+
+    #[test]
+    fn every_setting_changes_the_fingerprint() {
+        let base = || schema_generator(BASE);
+        let variants = vec![
+            base().generated_lib_name("other"),
+            base().output_file_name("other.rs"),
+            base().export_providers(),
+            base().no_std(true),
+            base().max_level(LogLevel::Warning),
+            base().builders(true),
+            base().redaction_policy(RedactionPolicy::NoWrite),
+            base().redaction_policy(RedactionPolicy::KeyedHash("APP_TRACE_KEY")),
+            base().runtime_registry(true),
+            base().include_dir("/opt/lttng/include"),
+            base().define("NDEBUG", None),
+            base().define("NDEBUG", Some("1")),
+            base().c_flag("-O3"),
+            base().clang_arg("-fno-inline"),
+        ];
+        let mut fingerprints: Vec<String> = variants.iter().map(fingerprint_of).collect();
+        fingerprints.push(fingerprint_of(&base()));
+        fingerprints.push(fingerprint_with(&base(), &Some("aarch64-unknown-linux-gnu".into()), &None));
+        fingerprints.push(fingerprint_with(&base(), &None, &Some("/sysroot".into())));
+        assert_distinct(&fingerprints);
+
+        // Settings that don't influence the outputs leave it alone
+        let unchanged = vec![
+            base().out_dir("/elsewhere"),
+            base().lttng_ust_link_mode(LinkMode::Static),
+            base().lttng_ust_lib_dir("/opt/lttng/lib"),
+            base().emit_rerun_directives(false),
+        ];
+        for generator in &unchanged {
+            assert_eq!(fingerprint_of(generator), fingerprint_of(&base()));
+        }
+    }
+
+    #[test]
+    fn merges_providers_defined_by_several_crates() {
+        let providers = |schemas: &[&str]| {
+            aggregate::merge(schemas.iter().flat_map(|s| schema::parse(s).unwrap()).collect())
+        };
+        const FIRST: &str = "provider p\n    common id integer u64\n    class c\n        instance i";
+        const SECOND: &str = "provider p\n    common id integer u64\n    class d\n        instance j";
+        let merged = providers(&[FIRST, SECOND, FIRST]).unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].classes.iter().map(|c| &c.class_name[..]).collect::<Vec<_>>(), ["c", "d"]);
+
+        match providers(&[FIRST, &FIRST.replace("instance i", "instance k")]) {
+            Err(Error::ConflictingClass(ref name)) => assert_eq!(name, "p:c"),
+            other => panic!("expected ConflictingClass, got {:?}", other.map(|_| ())),
+        }
+        match providers(&[FIRST, &SECOND.replace("u64", "u32")]) {
+            Err(Error::ConflictingCommonFields(ref name)) => assert_eq!(name, "p"),
+            other => panic!("expected ConflictingCommonFields, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn every_schema_attribute_changes_the_fingerprint() {
+        let edits: &[(&str, &str)] = &[
+            ("", ""),
+            ("provider p", "provider q"),
+            ("common id integer u64", "common id integer u32"),
+            ("common id integer u64", "common id optional integer u64"),
+            ("common id integer u64", "common id integer u64\n        description d"),
+            ("common id integer u64", "common id integer u64\n        unit ns"),
+            ("common id integer u64", "common id integer u64\n        sensitive"),
+            ("class c", "class d"),
+            ("class c", "class c\n        description d"),
+            ("field f integer u32", "field g integer u32"),
+            ("field f integer u32", "field f integer_hex u32"),
+            ("field f integer u32", "field f array u32 2"),
+            ("field f integer u32", "field f array u32 3"),
+            ("field f integer u32", "field f optional integer u32"),
+            ("field f integer u32", "field f serde cbor T"),
+            ("field f integer u32", "field f serde bincode T"),
+            ("field f integer u32", "field f serde cbor U"),
+            ("field f integer u32", "field f integer u32\n            description d"),
+            ("field f integer u32", "field f integer u32\n            unit ns"),
+            ("field f integer u32", "field f integer u32\n            sensitive"),
+            ("field f integer u32", "field f integer u32\n        location"),
+            ("instance i", "instance j"),
+            ("instance i", "instance i info"),
+            ("instance i", "instance i\n            description d"),
+            ("instance i", "instance i\n            emf_uri u"),
+            ("instance i", "instance i\n            sample 2"),
+            ("instance i", "instance i\n            sample 3"),
+            ("instance i", "instance i\n            rate_limit 1 2"),
+            ("instance i", "instance i\n            rate_limit 2 2"),
+            ("instance i", "instance i\n            rate_limit 1 3"),
+            ("instance i", "instance i\n        instance j"),
+        ];
+        let fingerprints: Vec<String> = edits.iter()
+            .map(|&(from, to)| {
+                let text = BASE.replacen(from, to, 1);
+                assert!(from.is_empty() || text != BASE);
+                fingerprint_of(&schema_generator(&text))
+            })
+            .collect();
+        assert_distinct(&fingerprints);
+    }
+
+    #[test]
+    fn exported_providers_lower_to_the_same_c_fields() {
+        let mut provider = Provider::new("exported");
+        provider.create_class("early")
+            .add_field("count", CTFType::Integer(CIntegerType::U32))
+            .instantiate("early");
+        provider.add_common_fields(FieldGroup::new()
+            .add_field("tenant", CTFType::SequenceText)
+            .add_optional_field("shard", CTFType::Integer(CIntegerType::U16))
+            .mark_sensitive("tenant"));
+        provider.create_class("late")
+            .add_field("big", CTFType::U128)
+            .add_field("elapsed", CTFType::Duration)
+            .add_field("peer", CTFType::Ipv6Addr)
+            .add_optional_field("flag", CTFType::Bool)
+            .add_serde_field("config", SerdeEncoding::Cbor, "Config")
+            .add_field("what", CTFType::Display)
+            .add_caller_location()
+            .instantiate("late");
+        let providers = vec![provider];
+
+        let path = env::temp_dir().join(format!("lttng-ust-generate-export-{}.schema", std::process::id()));
+        let mut exported = Vec::new();
+        schema::write(&mut exported, &providers).unwrap();
+        fs::write(&path, exported).unwrap();
+        let importer = Generator { imported_schema_files: vec![path.clone()], ..Generator::default() };
+        let imported = importer.c_providers(&[]);
+        fs::remove_file(&path).unwrap();
+        let imported = imported.unwrap();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].classes.len(), providers[0].classes.len());
+        for (class, imported_class) in providers[0].classes.iter().zip(&imported[0].classes) {
+            assert_eq!(imported_class.c_fields(), class.c_fields(), "class {}", class.class_name);
+        }
+    }
+
+    #[test]
+    fn builders_reject_fields_named_after_their_methods() {
+        let builders = |field: &str| Generator::default()
+            .builders(true)
+            .register_provider(schema::parse(&BASE.replace("field f", &format!("field {}", field))).unwrap()
+                               .remove(0));
+        match builders("emit").generate_sources() {
+            Err(Error::UnusableSetter(ref name)) => assert_eq!(name, "p:c.emit"),
+            other => panic!("expected UnusableSetter, got {:?}", other.map(|_| ())),
+        }
+        assert!(rust_bindings::first_unusable_setter(&builders("type").providers).is_none());
+        assert!(rust_bindings::first_unusable_setter(&builders("enabled").providers).is_some());
+    }
+
+    #[test]
+    fn no_std_rejects_fields_that_need_std() {
+        for field in &["path", "os_str", "serde cbor T"] {
+            let text = BASE.replace("field f integer u32", &format!("field f {}", field));
+            match schema_generator(&text).no_std(true).generate_sources() {
+                Err(Error::FieldNeedsStd(ref name)) => assert_eq!(name, "p:c.f"),
+                other => panic!("expected FieldNeedsStd for {}, got {:?}", field, other.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_lazily_formatted_optional_fields() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_optional_field("what", CTFType::Display)
+            .instantiate("i");
+        match Generator::default().register_provider(provider).generate_sources() {
+            Err(Error::UnsupportedOptional(ref name)) => assert_eq!(name, "p:c.what"),
+            other => panic!("expected UnsupportedOptional, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn counts_the_arguments_fields_add_behind_the_scenes() {
+        let provider_with = |customize: &dyn Fn(&mut EventClass)| {
+            let mut provider = Provider::new("p");
+            provider.add_common_fields(FieldGroup::new().add_field("id", CTFType::Integer(CIntegerType::U64)));
+            customize(provider.create_class("c").instantiate("i"));
+            provider
+        };
+        // 1 common field + 4 for the location + 2 for the sequence + 3 for the optional u128
+        let at_limit = |c: &mut EventClass| {
+            c.add_caller_location()
+                .add_field("payload", CTFType::Sequence(CIntegerType::U8))
+                .add_optional_field("total", CTFType::U128);
+        };
+        let overflows: &[&dyn Fn(&mut EventClass)] = &[
+            &|c| { at_limit(c); c.add_field("more", CTFType::Bool); },
+            &|c| { c.add_caller_location().add_field("a", CTFType::SequenceText).add_field("b", CTFType::Debug)
+                    .add_optional_field("c", CTFType::Bool); },
+            &|c| { c.add_field("a", CTFType::SocketAddr).add_field("b", CTFType::I128).add_field("c", CTFType::Path)
+                    .add_optional_field("d", CTFType::Char).add_field("e", CTFType::U128); },
+        ];
+        for overflow in overflows {
+            match Generator::default().register_provider(provider_with(*overflow)).generate_sources() {
+                Err(Error::TooManyArguments(ref name)) => assert_eq!(name, "p:c"),
+                other => panic!("expected TooManyArguments, got {:?}", other.map(|_| ())),
+            }
+        }
+        assert_eq!(tracepoint_impl::first_overlong_class(&[provider_with(&at_limit)]), None);
+    }
+
+    #[test]
+    fn derived_classes_only_take_the_derived_fields() {
+        const FIELDS: &[(&str, &str)] = &[("id", "integer u64"), ("status", "optional integer u16")];
+        let generate = |customize: &dyn Fn(&mut Provider)| {
+            let mut provider = Provider::new("p");
+            customize(&mut provider);
+            Generator::default().register_provider(provider).generate_sources().map(|_| ())
+        };
+        let mismatches: &[&dyn Fn(&mut Provider)] = &[
+            &|p| {
+                p.add_common_fields(FieldGroup::new().add_field("tenant", CTFType::SequenceText));
+                p.create_class("c").add_derived_fields(FIELDS).instantiate("i");
+            },
+            &|p| {
+                p.create_class("c").add_derived_fields(FIELDS).instantiate("i");
+                p.add_common_fields(FieldGroup::new().add_field("tenant", CTFType::SequenceText));
+            },
+            &|p| {
+                p.create_class("c").add_derived_fields(FIELDS).add_caller_location().instantiate("i");
+            },
+            &|p| {
+                p.create_class("c").add_derived_fields(FIELDS).add_field("x", CTFType::Bool).instantiate("i");
+            },
+            &|p| {
+                p.create_class("c").add_derived_fields(FIELDS).instantiate("i").sample_instance("i", 2);
+            },
+        ];
+        for mismatch in mismatches {
+            match generate(*mismatch) {
+                Err(Error::DerivedClassMismatch(ref name)) => assert_eq!(name, "p:c"),
+                other => panic!("expected DerivedClassMismatch, got {:?}", other),
+            }
+        }
+
+        let mut provider = Provider::new("p");
+        provider.create_class("c").add_derived_fields(FIELDS).instantiate("i");
+        assert!(provider.classes[0].matches_derived_fields());
+    }
+
+    #[test]
+    fn serde_fields_are_recorded_under_their_encoding() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_serde_field("config", SerdeEncoding::Cbor, "Config")
+            .add_serde_field("state", SerdeEncoding::Bincode, "State");
+        let names: Vec<String> = provider.classes[0].c_fields().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, ["config_cbor", "state_bincode"]);
+
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_field("config", CTFType::Serde(SerdeEncoding::Cbor))
+            .instantiate("i");
+        match Generator::default().register_provider(provider).generate_sources() {
+            Err(Error::MissingSerdeType(ref name)) => assert_eq!(name, "p:c.config"),
+            other => panic!("expected MissingSerdeType, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn keyed_hash_reads_its_key_at_runtime() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_field("user", CTFType::String)
+            .mark_sensitive("user")
+            .instantiate("i");
+        let mut c_code = Vec::new();
+        tracepoint_interface::generate_interface_impl(&mut c_code, &[provider], "in.h", "tp.h",
+                                                      RedactionPolicy::KeyedHash("APP_TRACE_KEY"))
+            .unwrap();
+        let c_code = String::from_utf8(c_code).unwrap();
+        assert!(c_code.contains("#define RUST_REDACTION_KEY_VAR \"APP_TRACE_KEY\""));
+        assert!(c_code.contains("getenv(RUST_REDACTION_KEY_VAR)"));
+        assert!(c_code.contains("rust_redaction_hash(user_arg, strlen(user_arg))"));
+    }
+
+    #[test]
+    fn text_arrays_and_nowrite_sequences_use_the_right_macros() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_field("name", CTFType::ArrayText(8))
+            .add_field("bytes", CTFType::SequenceNoWrite(CIntegerType::U8))
+            .instantiate("i");
+        let mut c_code = Vec::new();
+        tracepoint_impl::generate_tp_header(&mut c_code, &[provider], "tp.h", RedactionPolicy::Verbatim)
+            .unwrap();
+        let c_code = String::from_utf8(c_code).unwrap();
+        assert!(c_code.contains("ctf_array_text(char, name, name_arg, 8)"));
+        assert!(c_code.contains("ctf_sequence_nowrite(uint8_t, bytes, bytes_arg, size_t, bytes_len)"));
+    }
+
+    #[test]
+    fn optional_sequence_bindings_compile() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_optional_field("bytes", CTFType::Sequence(CIntegerType::U8))
+            .instantiate("i");
+        let dir = env::temp_dir().join(format!("lttng-ust-generate-bindings-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("bindings.rs"), rust_bindings_with_abi(&[provider], None)).unwrap();
+        fs::write(dir.join("raw.rs"), "extern \"C\" {\n\
+            \x20   pub fn p_c_i_tp(bytes_present: u8, bytes_arg: *const u8, bytes_len: usize);\n\
+            \x20   pub fn p_c_i_tp_enabled() -> ::std::os::raw::c_int;\n\
+            }\n").unwrap();
+        fs::write(dir.join("lib.rs"), "mod tracepoints { include!(\"bindings.rs\"); }\n\
+            pub fn fire(bytes: Option<&[u8]>) { tracepoints::p::i(bytes) }\n").unwrap();
+        let status = std::process::Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()))
+            .args(["--crate-type", "lib", "--emit", "metadata", "--out-dir"])
+            .arg(&dir)
+            .arg(dir.join("lib.rs"))
+            .status()
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(status.success());
+    }
+
+    fn rust_bindings_of(providers: &[Provider]) -> String {
+        rust_bindings_with_abi(providers, Some(UstAbi::V2_12))
+    }
+
+    fn rust_bindings_with_abi(providers: &[Provider], abi: Option<UstAbi>) -> String {
+        let options = rust_bindings::BindingOptions {
+            no_std: false, max_level: None, builders: false, runtime_registry: false,
+            unsafe_attributes: true, abi,
+        };
+        let mut out = Vec::new();
+        rust_bindings::generate_rust_bindings(&mut out, providers, "\"raw.rs\"", &options).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn registry_lists_generated_events() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .add_field("size", CTFType::Integer(CIntegerType::U32))
+            .add_optional_field("retries", CTFType::Integer(CIntegerType::U8))
+            .instantiate_with_level("shown", LogLevel::Info)
+            .instantiate_with_level("compiled_out", LogLevel::Debug);
+        let options = rust_bindings::BindingOptions {
+            no_std: false, max_level: Some(LogLevel::Info), builders: false, runtime_registry: true,
+            unsafe_attributes: false, abi: Some(UstAbi::V2_12),
+        };
+        let mut out = Vec::new();
+        rust_bindings::generate_rust_bindings(&mut out, &[provider], "\"raw.rs\"", &options).unwrap();
+        let bindings = String::from_utf8(out).unwrap();
+        assert!(bindings.contains("static EVENTS: [EventDescriptor; 1] = [\n\
+                                   \x20       EventDescriptor::new(\"p\", \"c\", \"shown\", LogLevel::Info,\n\
+                                   \x20           &[FieldDescriptor::new(\"size\", \"integer u32\", None), \
+                                   FieldDescriptor::new(\"retries\", \"optional integer u8\", \
+                                   Some(\"retries_present\"))],"));
+        assert!(bindings.contains("    extern crate lttng_ust;\n"));
+        assert!(bindings.contains("    #[link_section = \".init_array\"]\n"));
+
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .instantiate("i");
+        let generator = Generator::default()
+            .runtime_registry(true)
+            .target("x86_64-pc-windows-msvc")
+            .register_provider(provider);
+        match generator.generate_sources() {
+            Err(Error::RegistryNeedsInitArray(ref target)) => assert_eq!(target, "x86_64-pc-windows-msvc"),
+            other => panic!("expected RegistryNeedsInitArray, got {:?}", other.map(|_| ())),
+        }
+        for target in &["x86_64-unknown-linux-gnu", "aarch64-linux-android", "x86_64-unknown-freebsd",
+                        "x86_64-unknown-illumos", "riscv64gc-unknown-linux-musl"] {
+            assert!(toolchain::has_init_array(target), "{}", target);
+        }
+        for target in &["aarch64-apple-darwin", "x86_64-pc-windows-gnu", "wasm32-unknown-unknown",
+                        "thumbv7em-none-eabihf"] {
+            assert!(!toolchain::has_init_array(target), "{}", target);
+        }
+    }
+
+    #[test]
+    fn state_is_loaded_atomically_from_the_tracepoint_struct() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .instantiate("i");
+        let providers = [provider];
+        let bindings = rust_bindings_of(&providers);
+        assert!(bindings.contains("(*::std::ptr::addr_of!(super::detail::__tracepoint_p___i.state)\
+                                   .cast::<::std::sync::atomic::AtomicI32>())\
+                                   .load(::std::sync::atomic::Ordering::Relaxed) == 0"));
+        assert!(!bindings.contains("read_volatile"));
+
+        // lttng-ust 2.13 renamed the struct, and without headers we can't tell which it is
+        let bindings = rust_bindings_with_abi(&providers, Some(UstAbi::V2_13));
+        assert!(bindings.contains("addr_of!(super::detail::lttng_ust_tracepoint_p___i.state)"));
+        let bindings = rust_bindings_with_abi(&providers, None);
+        assert!(bindings.contains("if unsafe { super::detail::p_c_i_tp_enabled() == 0 }"));
+        assert!(!bindings.contains("tracepoint_p___i"));
+
+        let mut c_code = Vec::new();
+        tracepoint_interface::generate_interface_impl(&mut c_code, &providers, "in.h", "tp.h",
+                                                      RedactionPolicy::Verbatim).unwrap();
+        tracepoint_interface::generate_interface_header(&mut c_code, &providers, "tracepoints").unwrap();
+        let c_code = String::from_utf8(c_code).unwrap();
+        assert!(c_code.contains("int p_c_i_tp_enabled(void)"));
+        assert!(!c_code.contains("_state"));
+        assert!(!c_code.contains("__tracepoint_"));
+    }
+
+    #[test]
+    fn link_mode_follows_lttng_ust_sys() {
+        let some = |value: &str| Some(value.into());
+        assert_eq!(toolchain::link_mode_from(None, None), LinkMode::Dynamic);
+        assert_eq!(toolchain::link_mode_from(some("1"), None), LinkMode::Static);
+        assert_eq!(toolchain::link_mode_from(some("0"), some("1")), LinkMode::Dynamic);
+        assert_eq!(toolchain::link_mode_from(None, some("yes")), LinkMode::Static);
+        assert_eq!(toolchain::link_mode_from(None, some("0")), LinkMode::Dynamic);
+    }
+
+    #[test]
+    fn lttng_ust_version_is_read_from_its_header() {
+        let header = "#ifndef _LTTNG_UST_VERSION_H\n\
+                      #define LTTNG_UST_MAJOR_VERSION 2\n\
+                      #define LTTNG_UST_MINOR_VERSION 13\n\
+                      #define LTTNG_UST_PATCHLEVEL_VERSION 7\n";
+        let dir = env::temp_dir().join(format!("lttng-ust-generate-{}", std::process::id()));
+        let path = dir.join("lttng/ust-version.h");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, header).unwrap();
+        let version = toolchain::lttng_ust_version(&[PathBuf::from("/nonexistent/tracepoint.h"), path]);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(version, Some((2, 13)));
+        assert_eq!(version.map(UstAbi::from_version), Some(UstAbi::V2_13));
+        assert_eq!(UstAbi::from_version((2, 12)), UstAbi::V2_12);
+        assert_eq!(toolchain::lttng_ust_version(&[]), None);
+    }
+
+    #[test]
+    fn only_rate_limiting_reports_suppressed_events() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c")
+            .instantiate("sampled")
+            .instantiate("limited")
+            .sample_instance("sampled", 10)
+            .sample_instance("limited", 10)
+            .rate_limit_instance("limited", 100, 10);
+        let providers = limits::with_suppression_events(&[provider]).unwrap();
+        let class_names: Vec<&str> = providers[0].classes.iter().map(|c| &c.class_name[..]).collect();
+        assert_eq!(class_names, ["c", "c_suppressed"]);
+        let instance_names: Vec<&str> = providers[0].classes[1].instances.iter().map(|i| &i.name[..]).collect();
+        assert_eq!(instance_names, ["limited_suppressed"]);
+
+        let bindings = rust_bindings_of(&providers);
+        assert!(!bindings.contains("p_c_suppressed_sampled_suppressed_tp"));
+        assert!(bindings.contains("p_c_suppressed_limited_suppressed_tp(suppressed)"));
+    }
+
+    #[test]
+    fn suppression_events_record_common_fields() {
+        let mut group = FieldGroup::new();
+        group.add_field("request_id", CTFType::Integer(CIntegerType::U64));
+        let mut provider = Provider::new("p");
+        provider.add_common_fields(&group)
+            .create_class("c")
+            .add_field("size", CTFType::Integer(CIntegerType::U32))
+            .instantiate("i")
+            .rate_limit_instance("i", 100, 10);
+        let providers = limits::with_suppression_events(&[provider]).unwrap();
+        let field_names: Vec<&str> = providers[0].classes[1].fields.iter().map(|f| &f.name[..]).collect();
+        assert_eq!(field_names, ["request_id", "count"]);
+        assert!(rust_bindings_of(&providers).contains("p_c_suppressed_i_suppressed_tp(a0, suppressed)"));
+    }
+
+    #[test]
+    fn suppression_events_dont_replace_user_events() {
+        for &(class, instance) in &[("c_suppressed", "j"), ("d", "i_suppressed")] {
+            let mut provider = Provider::new("p");
+            provider.create_class("c")
+                .instantiate("i")
+                .rate_limit_instance("i", 100, 10);
+            provider.create_class(class)
+                .instantiate(instance);
+            match Generator::default().register_provider(provider).generate_sources() {
+                Err(Error::SuppressionNameTaken(ref name)) =>
+                    assert!(name == "p:c_suppressed" || name == "p:i_suppressed", "{}", name),
+                other => panic!("expected SuppressionNameTaken, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    fn assert_distinct(fingerprints: &[String]) {
+        for (i, a) in fingerprints.iter().enumerate() {
+            for (j, b) in fingerprints.iter().enumerate().skip(i + 1) {
+                assert_ne!(a, b, "variants {} and {} share a fingerprint", i, j);
+            }
+        }
     }
 }
@@ -0,0 +1,198 @@
+use std::io;
+use std::io::prelude::*;
+
+use ::{CIntegerType, CTFType, Field, Provider};
+
+/// What the tracepoint library records for fields marked with
+/// [`EventClass::mark_sensitive`](::EventClass::mark_sensitive)
+#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+pub enum RedactionPolicy {
+    /// Record sensitive fields like any other field. This is the default.
+    Verbatim,
+    /// Record a SipHash-2-4 of sensitive fields instead of their value, as a `uint64_t`
+    /// printed in hex. Equal values still hash equally within a trace (and across traces
+    /// using the same key), so they can be correlated without being revealed.
+    ///
+    /// The key is never part of the tracepoint library: it is read from the named
+    /// environment variable, as 32 hex digits, when the library is loaded. **If the variable
+    /// is unset or malformed, a random key is used instead**, so hashes only match within
+    /// one process; and if no random key can be had either, sensitive fields are recorded
+    /// as 0.
+    KeyedHash(&'static str),
+    /// Make sensitive fields `*_nowrite`: event filters can still use them, but they are
+    /// never written to the trace.
+    NoWrite,
+}
+
+/// The fields lttng-ust should record for `fields`, which come from `EventClass::c_fields`
+pub(in super) fn recorded_fields(fields: Vec<Field>, policy: RedactionPolicy) -> Vec<Field> {
+    fields.into_iter()
+        .map(|mut field| {
+            if field.sensitive {
+                match policy {
+                    RedactionPolicy::Verbatim => {}
+                    RedactionPolicy::KeyedHash(_) =>
+                        field.ctf_type = CTFType::IntegerHex(CIntegerType::U64),
+                    RedactionPolicy::NoWrite => field.ctf_type = nowrite(field.ctf_type),
+                }
+            }
+            field
+        })
+        .collect()
+}
+
+/// Whether the interface implementation needs the hash function
+pub(in super) fn needs_hash(providers: &[Provider], policy: RedactionPolicy) -> bool {
+    match policy {
+        RedactionPolicy::KeyedHash(_) => providers.iter()
+            .flat_map(|p| &p.classes)
+            .flat_map(|c| &c.fields)
+            .any(|f| f.sensitive),
+        _ => false,
+    }
+}
+
+/// The argument passed to `tracepoint()` in place of a sensitive `field` when hashing
+pub(in super) fn hashed_arg(field: &Field) -> String {
+    let name = &field.name;
+    let (data, len) = match field.ctf_type {
+        CTFType::String | CTFType::StringNoWrite =>
+            (format!("{}_arg", name), format!("strlen({}_arg)", name)),
+        CTFType::Array(_, l) | CTFType::ArrayNoWrite(_, l) |
+        CTFType::ArrayText(l) | CTFType::ArrayTextNoWrite(l) =>
+            (format!("{}_arg", name), format!("sizeof(*{0}_arg) * {1}", name, l)),
+        ty if ty.is_sequence() =>
+            (format!("{}_arg", name), format!("sizeof(*{0}_arg) * {0}_len", name)),
+        _ => (format!("&{}_arg", name), format!("sizeof({}_arg)", name)),
+    };
+    format!("rust_redaction_hash({}, {})", data, len)
+}
+
+/// SipHash-2-4, keyed from the environment when the library is loaded
+pub(in super) fn write_hash_function<F: Write>(outf: &mut F, policy: RedactionPolicy) -> io::Result<()> {
+    let key_var = match policy {
+        RedactionPolicy::KeyedHash(key_var) => key_var,
+        _ => return Ok(()),
+    };
+    writeln!(outf, "#include <stdio.h>")?;
+    writeln!(outf, "#include <stdlib.h>")?;
+    writeln!(outf, "#include <string.h>")?;
+    writeln!(outf)?;
+    writeln!(outf, "#define RUST_REDACTION_KEY_VAR {:?}", key_var)?;
+    write!(outf, "{}", SIPHASH)
+}
+
+const SIPHASH: &str = r#"
+static uint8_t rust_redaction_key[16];
+static int rust_redaction_keyed;
+
+static int rust_redaction_hex_digit(char c) {
+    if (c >= '0' && c <= '9') return c - '0';
+    if (c >= 'a' && c <= 'f') return c - 'a' + 10;
+    if (c >= 'A' && c <= 'F') return c - 'A' + 10;
+    return -1;
+}
+
+static int rust_redaction_parse_key(const char *hex) {
+    size_t i;
+    if (hex == NULL || strlen(hex) != 32) {
+        return 0;
+    }
+    for (i = 0; i < 16; i++) {
+        int high = rust_redaction_hex_digit(hex[2 * i]);
+        int low = rust_redaction_hex_digit(hex[2 * i + 1]);
+        if (high < 0 || low < 0) {
+            return 0;
+        }
+        rust_redaction_key[i] = (uint8_t)(high << 4 | low);
+    }
+    return 1;
+}
+
+/* Runs before any tracepoint can fire, so the key is never read concurrently */
+__attribute__((constructor))
+static void rust_redaction_init(void) {
+    FILE *random;
+    if (rust_redaction_parse_key(getenv(RUST_REDACTION_KEY_VAR))) {
+        rust_redaction_keyed = 1;
+        return;
+    }
+    random = fopen("/dev/urandom", "rb");
+    if (random != NULL) {
+        rust_redaction_keyed = fread(rust_redaction_key, 1, 16, random) == 16;
+        fclose(random);
+    }
+}
+
+#define RUST_ROTL(x, b) (uint64_t)(((x) << (b)) | ((x) >> (64 - (b))))
+#define RUST_SIPROUND \
+    do { \
+        v0 += v1; v1 = RUST_ROTL(v1, 13); v1 ^= v0; v0 = RUST_ROTL(v0, 32); \
+        v2 += v3; v3 = RUST_ROTL(v3, 16); v3 ^= v2; \
+        v0 += v3; v3 = RUST_ROTL(v3, 21); v3 ^= v0; \
+        v2 += v1; v1 = RUST_ROTL(v1, 17); v1 ^= v2; v2 = RUST_ROTL(v2, 32); \
+    } while (0)
+
+static uint64_t rust_redaction_load(const uint8_t *p, size_t len) {
+    uint64_t v = 0;
+    size_t i;
+    for (i = 0; i < len; i++) {
+        v |= (uint64_t)p[i] << (8 * i);
+    }
+    return v;
+}
+
+static uint64_t rust_redaction_hash(const void *data, size_t len) {
+    const uint8_t *in = (const uint8_t *)data;
+    uint64_t k0 = rust_redaction_load(rust_redaction_key, 8);
+    uint64_t k1 = rust_redaction_load(rust_redaction_key + 8, 8);
+    uint64_t v0 = k0 ^ 0x736f6d6570736575ULL;
+    uint64_t v1 = k1 ^ 0x646f72616e646f6dULL;
+    uint64_t v2 = k0 ^ 0x6c7967656e657261ULL;
+    uint64_t v3 = k1 ^ 0x7465646279746573ULL;
+    uint64_t m;
+    size_t left = len;
+    int i;
+    if (!rust_redaction_keyed) {
+        return 0;
+    }
+    for (; left >= 8; in += 8, left -= 8) {
+        m = rust_redaction_load(in, 8);
+        v3 ^= m;
+        RUST_SIPROUND;
+        RUST_SIPROUND;
+        v0 ^= m;
+    }
+    m = rust_redaction_load(in, left) | ((uint64_t)len << 56);
+    v3 ^= m;
+    RUST_SIPROUND;
+    RUST_SIPROUND;
+    v0 ^= m;
+    v2 ^= 0xff;
+    for (i = 0; i < 4; i++) {
+        RUST_SIPROUND;
+    }
+    return v0 ^ v1 ^ v2 ^ v3;
+}
+
+"#;
+
+/// The filter-only version of `ty`
+fn nowrite(ty: CTFType) -> CTFType {
+    match ty {
+        CTFType::Integer(i) |
+        CTFType::IntegerHex(i) |
+        CTFType::IntegerNetwork(i) |
+        CTFType::IntegerNetworkHex(i) => CTFType::IntegerNoWrite(i),
+        CTFType::Float(f) => CTFType::FloatNoWrite(f),
+        CTFType::String => CTFType::StringNoWrite,
+        CTFType::Array(i, l) => CTFType::ArrayNoWrite(i, l),
+        CTFType::ArrayText(l) => CTFType::ArrayTextNoWrite(l),
+        CTFType::Sequence(i) => CTFType::SequenceNoWrite(i),
+        CTFType::SequenceText |
+        CTFType::Display |
+        CTFType::Debug => CTFType::SequenceTextNoWrite,
+        CTFType::Enum => CTFType::EnumNoWrite,
+        ty => ty,
+    }
+}
@@ -1,39 +1,270 @@
-use std::fs::File;
 use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
 
-use ::{CTFType, EventClass, EventInstance, Field, Provider};
+use ::{CTFType, EventClass, EventInstance, Field, LogLevel, Provider, SerdeEncoding};
+use schema;
 
-use super::tracepoint_interface::generate_func_name;
+use super::limits::{limit_checks, with_suppressed_call, write_limits};
+use super::tracepoint_interface::{generate_enabled_func_name, generate_func_name, generate_tracepoint_var_name};
+use super::tracepoint_interface::UstAbi;
 
-pub(in super) fn generate_rust_bindings(output_path: &PathBuf,
-                                        providers: &[Provider],
-                                        raw_bindings: &PathBuf) -> io::Result<()> {
-    let mut outf = File::create(output_path)?;
-    write_include(&mut outf, raw_bindings)?;
-    write_providers(&mut outf, providers)?;
+/// The [`Generator`](::Generator) settings that affect the Rust side of the bindings
+pub(in super) struct BindingOptions {
+    /// Only refer to `core` in the generated code
+    pub(in super) no_std: bool,
+    /// Instances less severe than this compile to nothing
+    pub(in super) max_level: Option<LogLevel>,
+    /// Generate typed builders instead of positional functions
+    pub(in super) builders: bool,
+    /// Register every tracepoint with `lttng_ust::registry`
+    pub(in super) runtime_registry: bool,
+    /// Spell `link_section` as an unsafe attribute, which takes Rust 1.82 and is required by
+    /// the 2024 edition
+    pub(in super) unsafe_attributes: bool,
+    /// The ABI of the lttng-ust headers, `None` if they weren't found
+    pub(in super) abi: Option<UstAbi>,
+}
+
+impl BindingOptions {
+    fn std_crate(&self) -> &'static str {
+        if self.no_std { "::core" } else { "::std" }
+    }
+
+    fn is_compiled_out(&self, instance: &EventInstance) -> bool {
+        self.max_level.is_some_and(|max| instance.level > max)
+    }
+}
+
+/// `raw_bindings` is the argument of the `include!` that pulls in the bindgen output
+pub(in super) fn generate_rust_bindings<F: Write>(outf: &mut F,
+                                                  providers: &[Provider],
+                                                  raw_bindings: &str,
+                                                  options: &BindingOptions) -> io::Result<()> {
+    write_include(outf, raw_bindings)?;
+    // Builders without fields never mention `Unset`
+    let has_fields = providers.iter().flat_map(|p| &p.classes).any(|c| !c.fields.is_empty());
+    if options.builders && has_fields {
+        write_unset(outf)?;
+    }
+    let has_lazy_text = providers.iter()
+        .flat_map(|p| &p.classes)
+        .flat_map(|c| &c.fields)
+        .any(|f| f.ctf_type.lazy_text_format().is_some());
+    if has_lazy_text {
+        write_lazy_text(outf, options)?;
+    }
+    let mut encodings: Vec<SerdeEncoding> = Vec::new();
+    for field in providers.iter().flat_map(|p| &p.classes).flat_map(|c| &c.fields) {
+        if let CTFType::Serde(encoding) = field.ctf_type {
+            if !encodings.contains(&encoding) {
+                encodings.push(encoding);
+            }
+        }
+    }
+    if !encodings.is_empty() {
+        write_serde_payload(outf, &encodings)?;
+    }
+    if providers.iter().flat_map(|p| &p.classes).flat_map(|c| &c.instances).any(|i| i.is_limited()) {
+        write_limits(outf)?;
+    }
+    if options.runtime_registry {
+        write_registry(outf, providers, options)?;
+    }
+    write_providers(outf, providers, options)?;
 
     Ok(())
 }
 
-fn write_include<F: Write>(outf: &mut F, raw_bindings_path: &PathBuf) -> io::Result<()> {
+#[allow(clippy::write_with_newline)]
+fn write_include<F: Write>(outf: &mut F, raw_bindings_path: &str) -> io::Result<()> {
     write!(outf, "#[allow(non_upper_case_globals)]\n")?;
     write!(outf, "#[allow(non_camel_case_types)]\n")?;
     write!(outf, "#[allow(non_snake_case)]\n")?;
+    // Compiled out tracepoints never call into C
+    writeln!(outf, "#[allow(dead_code)]")?;
     write!(outf, "mod detail {{\n")?;
-    write!(outf, "    include!(\"{}\");\n", raw_bindings_path.to_string_lossy())?;
+    writeln!(outf, "    include!({});", raw_bindings_path)?;
     write!(outf, "}}\n")?;
 
     Ok(())
 }
 
-fn write_providers<F: Write>(outf: &mut F, providers: &[Provider]) -> io::Result<()> {
+fn write_unset<F: Write>(outf: &mut F) -> io::Result<()> {
+    writeln!(outf, "/// Marks a tracepoint field that hasn't been set yet")?;
+    writeln!(outf, "pub(in super) struct Unset;")?;
+
+    Ok(())
+}
+
+/// Helpers for `Display` and `Debug` fields, which are formatted into a scratch buffer right
+/// before calling into C. The buffers are thread-local and reused, unless we're `no_std`, in
+/// which case they live on the stack and long text gets truncated.
+fn write_lazy_text<F: Write>(outf: &mut F, options: &BindingOptions) -> io::Result<()> {
+    let module = if options.no_std {
+        r#"
+mod lazy_text {
+    use ::core::fmt::{self, Write};
+
+    const CAPACITY: usize = 256;
+
+    struct Buffer {
+        bytes: [u8; CAPACITY],
+        len: usize,
+    }
+
+    impl Write for Buffer {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let mut take = s.len().min(CAPACITY - self.len);
+            while !s.is_char_boundary(take) {
+                take -= 1;
+            }
+            self.bytes[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+            self.len += take;
+            Ok(())
+        }
+    }
+
+    pub(in super) fn with_text<R, F: FnOnce(&str) -> R>(args: fmt::Arguments, f: F) -> R {
+        let mut buffer = Buffer { bytes: [0; CAPACITY], len: 0 };
+        let _ = buffer.write_fmt(args);
+        f(::core::str::from_utf8(&buffer.bytes[..buffer.len]).unwrap_or(""))
+    }
+}
+"#
+    } else {
+        r#"
+mod lazy_text {
+    use ::std::cell::RefCell;
+    use ::std::fmt::{self, Write};
+
+    thread_local! {
+        // A stack rather than a single buffer, since formatting may fire tracepoints too
+        static BUFFERS: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    }
+
+    pub(in super) fn with_text<R, F: FnOnce(&str) -> R>(args: fmt::Arguments, f: F) -> R {
+        let mut buffer = BUFFERS.try_with(|b| b.borrow_mut().pop())
+            .ok()
+            .and_then(|b| b)
+            .unwrap_or_default();
+        buffer.clear();
+        let _ = buffer.write_fmt(args);
+        let result = f(&buffer);
+        let _ = BUFFERS.try_with(|b| b.borrow_mut().push(buffer));
+        result
+    }
+}
+"#
+    };
+    write!(outf, "{}", module)
+}
+
+/// Helpers for `Serde` fields, which are encoded into reused thread-local buffers right
+/// before calling into C. Only the encodings in use are emitted, so that crates only need to
+/// depend on the encoding crates they actually use.
+fn write_serde_payload<F: Write>(outf: &mut F, encodings: &[SerdeEncoding]) -> io::Result<()> {
+    let module = r#"
+mod serde_payload {
+    use ::std::cell::RefCell;
+
+    thread_local! {
+        // A stack rather than a single buffer, since encoding may fire tracepoints too
+        static BUFFERS: RefCell<Vec<Vec<u8>>> = RefCell::new(Vec::new());
+    }
+
+    /// Encoding failures leave the buffer empty
+    fn with_buffer<R, E, F>(encode: E, f: F) -> R
+        where E: FnOnce(&mut Vec<u8>) -> bool, F: FnOnce(&[u8]) -> R
+    {
+        let mut buffer = BUFFERS.try_with(|b| b.borrow_mut().pop())
+            .ok()
+            .and_then(|b| b)
+            .unwrap_or_default();
+        buffer.clear();
+        if !encode(&mut buffer) {
+            buffer.clear();
+        }
+        let result = f(&buffer);
+        let _ = BUFFERS.try_with(|b| b.borrow_mut().push(buffer));
+        result
+    }
+"#;
+    write!(outf, "{}", module)?;
+    for encoding in encodings {
+        let encode = match *encoding {
+            SerdeEncoding::Cbor => "::ciborium::ser::into_writer(value, buffer).is_ok()",
+            SerdeEncoding::Bincode => "::bincode::serialize_into(buffer, value).is_ok()",
+        };
+        write!(outf, r"
+    pub(in super) fn with_{}<T, R, F>(value: &T, f: F) -> R
+        where T: ?Sized + ::serde::Serialize, F: FnOnce(&[u8]) -> R
+    {{
+        with_buffer(|buffer| {}, f)
+    }}
+", encoding.name(), encode)?;
+    }
+    writeln!(outf, "}}")
+}
+
+/// A descriptor for every tracepoint that isn't compiled out, registered with
+/// `lttng_ust::registry` from `.init_array` when the program starts. The `extern crate`
+/// spares 2015 edition crates from declaring `lttng_ust` themselves.
+fn write_registry<F: Write>(outf: &mut F, providers: &[Provider], options: &BindingOptions) -> io::Result<()> {
+    writeln!(outf, "mod registry {{")?;
+    writeln!(outf, "    extern crate lttng_ust;")?;
+    writeln!(outf, "    use self::lttng_ust::registry::{{EventDescriptor, FieldDescriptor, LogLevel, Registration}};")?;
+    writeln!(outf)?;
+    let mut events = Vec::new();
+    for provider in providers {
+        for class in &provider.classes {
+            for instance in class.instances.iter().filter(|i| !options.is_compiled_out(i)) {
+                let fields = class.fields.iter()
+                    .map(|f| Ok(format!("FieldDescriptor::new({:?}, {:?}, {:?})",
+                                        f.name, schema::field_type_name(f)?, f.presence_name())))
+                    .collect::<io::Result<Vec<_>>>()?;
+                events.push(format!("        EventDescriptor::new({:?}, {:?}, {:?}, LogLevel::{:?},\n", provider.name,
+                                    class.class_name, instance.name, instance.level) +
+                            &format!("            &[{}],\n", fields.join(", ")) +
+                            &format!("            || unsafe {{ {} != 0 }}),\n",
+                                    state_word(provider, class, instance, options)));
+            }
+        }
+    }
+    writeln!(outf, "    static EVENTS: [EventDescriptor; {}] = [", events.len())?;
+    for event in &events {
+        write!(outf, "{}", event)?;
+    }
+    writeln!(outf, "    ];")?;
+    let rest = r#"    static REGISTRATION: Registration = Registration::new(&EVENTS);
+
+    extern "C" fn register() {
+        lttng_ust::registry::register(&REGISTRATION);
+    }
+
+    #[used]
+"#;
+    write!(outf, "{}", rest)?;
+    if options.unsafe_attributes {
+        writeln!(outf, "    #[unsafe(link_section = \".init_array\")]")?;
+    } else {
+        writeln!(outf, "    #[link_section = \".init_array\"]")?;
+    }
+    writeln!(outf, "    static REGISTER: extern \"C\" fn() = register;")?;
+    writeln!(outf, "}}")
+}
+
+#[allow(clippy::write_with_newline)]
+fn write_providers<F: Write>(outf: &mut F, providers: &[Provider],
+                             options: &BindingOptions) -> io::Result<()> {
     for provider in providers {
         write!(outf, "pub(in super) mod {} {{", provider.name)?;
         for event_class in &provider.classes {
             for instance in &event_class.instances {
-                let f = generate_instance_call(provider, event_class, instance);
+                let f = if options.builders {
+                    generate_instance_builder(provider, event_class, instance, options)
+                } else {
+                    generate_instance_call(provider, event_class, instance, options)
+                };
                 write!(outf, "{}\n", f)?;
             }
         }
@@ -42,30 +273,383 @@ fn write_providers<F: Write>(outf: &mut F, providers: &[Provider]) -> io::Result
     Ok(())
 }
 
-fn generate_instance_call(provider: &Provider, class: &EventClass, instance: &EventInstance) -> String {
+fn generate_instance_call(provider: &Provider, class: &EventClass, instance: &EventInstance,
+                          options: &BindingOptions) -> String {
     let name = &instance.name;
     let type_args = "";
     let args: Vec<String> = class.fields.iter().enumerate()
         .map(|(i, field)| {
-            format!("a{}: {}", i, rust_type_for(&field.ctf_type))
+            format!("a{}: {}", i, rust_param_type(field, options.std_crate(), ""))
         }).collect();
     let args = &args.join(", ");
-    let c_args: Vec<String> = class.fields.iter().enumerate()
-        .map(|(i, field)| {
-            c_arg_for_field(format!("a{}", i), field)
-        }).collect();
-    let c_args = &c_args.join(", ");
-    let native_name = generate_func_name(provider, class, instance);
-    format!(r"
+    if options.is_compiled_out(instance) {
+        // Keep the signature so call sites still type check, but don't call into C
+        return format!(r"
+        {}#[inline(always)]
+        #[allow(unused_variables)]
         pub(in super::super) fn {}<{}>({}) {{
-            unsafe {{
-                super::detail::{}({})
-            }}
         }}
-", name, type_args, args, native_name, c_args)
+", instance_docs(class, instance, true, "        "), name, type_args, args);
+    }
+    let values: Vec<String> = (0..class.fields.len()).map(|i| format!("a{}", i)).collect();
+    format!(r"
+        {}#[inline]
+        {}pub(in super::super) fn {}<{}>({}) {{
+            {}
+        }}
+", instance_docs(class, instance, true, "        "), track_caller(class, "        "), name, type_args, args,
+            generate_native_call(provider, class, instance, &values, options, "            "))
 }
 
-fn rust_type_for(ty: &CTFType) -> String {
+/// The body of a generated function, calling into C with the field `values` if the
+/// tracepoint is enabled. `Display`, `Debug` and `Serde` fields are only formatted after that
+/// check, and so are the limits of sampled and rate limited instances.
+fn generate_native_call(provider: &Provider, class: &EventClass, instance: &EventInstance,
+                        values: &[String], options: &BindingOptions, indent: &str) -> String {
+    let native_name = generate_func_name(provider, class, instance);
+    let mut optional_bindings = String::new();
+    let std_crate = options.std_crate();
+    let mut c_args: Vec<String> = class.fields.iter().zip(values)
+        .enumerate()
+        .map(|(i, (field, value))| {
+            if field.ctf_type.lazy_text_format().is_some() {
+                c_arg_for_field(format!("text{}", i), field, std_crate)
+            } else if let CTFType::Serde(_) = field.ctf_type {
+                c_arg_for_field(format!("bytes{}", i), field, std_crate)
+            } else if field.optional {
+                optional_bindings += &format!(
+                    "let (present{0}, value{0}) = match {1} {{ Some(v) => (1u8, v), None => (0u8, {2}) }};\n{3}",
+                    i, value, zero_value(&field.ctf_type, std_crate), indent);
+                format!("present{}, {}", i, c_arg_for_field(format!("value{}", i), field, std_crate))
+            } else {
+                c_arg_for_field(value.clone(), field, std_crate)
+            }
+        }).collect();
+    c_args.extend(location_args(class));
+
+    let mut call = format!("unsafe {{ super::detail::{}({}) }}", native_name, c_args.join(", "));
+    call = with_suppressed_call(provider, class, instance, &c_args, call);
+    for (i, (field, value)) in class.fields.iter().zip(values).enumerate().rev() {
+        if let Some((spec, _)) = field.ctf_type.lazy_text_format() {
+            call = format!("super::lazy_text::with_text(format_args!(\"{}\", {}), |text{}| {})",
+                           spec, value, i, call);
+        } else if let CTFType::Serde(encoding) = field.ctf_type {
+            call = format!("super::serde_payload::with_{}({}, |bytes{}| {})",
+                           encoding.name(), value, i, call);
+        }
+    }
+
+    // Checked before anything else, so disabled tracepoints cost a load and a branch. Limits
+    // must only count hits while the tracepoint is enabled anyway.
+    let state = state_word(provider, class, instance, options);
+    let mut body = format!("if unsafe {{ {} == 0 }} {{\n{1}    return;\n{1}}}\n{1}", state, indent);
+    body += &caller_binding(class, options, indent);
+    body += &optional_bindings;
+    if instance.is_limited() {
+        body += &limit_checks(instance, indent);
+    }
+    body + &call
+}
+
+/// The lttng-ust state word of `instance`, nonzero while it is enabled. Read straight from
+/// the tracepoint struct rather than through a C function, and atomically, since lttng-ust
+/// updates it from its own threads. Without the headers to tell what the struct is called,
+/// the C `_enabled` function reads it instead.
+fn state_word(provider: &Provider, class: &EventClass, instance: &EventInstance,
+              options: &BindingOptions) -> String {
+    match options.abi {
+        Some(abi) => format!(
+            "(*{0}::ptr::addr_of!(super::detail::{1}.state).cast::<{0}::sync::atomic::AtomicI32>())\
+             .load({0}::sync::atomic::Ordering::Relaxed)",
+            options.std_crate(), generate_tracepoint_var_name(provider, instance, abi)),
+        None => format!("super::detail::{}()",
+                        generate_enabled_func_name(&generate_func_name(provider, class, instance))),
+    }
+}
+
+/// Doc comment lines for the descriptions of `class` and `instance`, along with the fields'
+/// descriptions and units if `list_fields` is set. Empty if nothing was described.
+fn instance_docs(class: &EventClass, instance: &EventInstance, list_fields: bool, indent: &str) -> String {
+    let mut paragraphs: Vec<Vec<String>> = class.description.iter()
+        .chain(&instance.description)
+        .map(|d| d.lines().map(String::from).collect())
+        .collect();
+    let fields: Vec<String> = class.fields.iter()
+        .filter_map(|f| field_doc(f).map(|doc| format!("* `{}`: {}", f.name, doc)))
+        .collect();
+    if list_fields && !fields.is_empty() {
+        paragraphs.push(fields);
+    }
+    let lines: Vec<String> = paragraphs.join(&String::new());
+    lines.iter().map(|line| format!("///{}{}\n{}", if line.is_empty() { "" } else { " " }, line, indent))
+        .collect()
+}
+
+/// The description and unit of `field`, if any
+fn field_doc(field: &Field) -> Option<String> {
+    match (&field.description, &field.unit) {
+        (Some(description), Some(unit)) => Some(format!("{} (in {})", description.lines().collect::<Vec<_>>().join(" "), unit)),
+        (Some(description), None) => Some(description.lines().collect::<Vec<_>>().join(" ")),
+        (None, Some(unit)) => Some(format!("In {}", unit)),
+        (None, None) => None,
+    }
+}
+
+fn track_caller(class: &EventClass, indent: &str) -> String {
+    if class.caller_location {
+        format!("#[track_caller]\n{}", indent)
+    } else {
+        String::new()
+    }
+}
+
+fn caller_binding(class: &EventClass, options: &BindingOptions, indent: &str) -> String {
+    if class.caller_location {
+        format!("let caller = {}::panic::Location::caller();\n{}", options.std_crate(), indent)
+    } else {
+        String::new()
+    }
+}
+
+/// The arguments for the synthetic fields added by `EventClass::add_caller_location`
+fn location_args(class: &EventClass) -> Vec<String> {
+    if class.caller_location {
+        vec![
+            "caller.file().as_ptr() as *const _".into(),
+            "caller.file().len()".into(),
+            "caller.line()".into(),
+            "caller.column()".into(),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+/// A typestate builder for one instance: every field starts out as `Unset`, and `emit` only
+/// exists once all of them have been given a value.
+fn generate_instance_builder(provider: &Provider, class: &EventClass, instance: &EventInstance,
+                             options: &BindingOptions) -> String {
+    let name = &instance.name;
+    let type_name = camel_case(name);
+    // Display and Debug fields get a type parameter of their own, bound by the trait
+    let field_types: Vec<String> = class.fields.iter().enumerate()
+        .map(|(i, field)| match field.ctf_type.lazy_text_format() {
+            Some(_) => format!("T{}", i),
+            None => rust_param_type(field, options.std_crate(), "'a "),
+        })
+        .collect();
+    let borrows: Vec<bool> = class.fields.iter().zip(&field_types)
+        .map(|(field, ty)| field.ctf_type.lazy_text_format().is_none() &&
+                           *ty != rust_param_type(field, options.std_crate(), ""))
+        .collect();
+    // The struct fields are positional, so field names only ever show up as setters
+    let members: Vec<String> = (0..class.fields.len()).map(|i| format!("f{}", i)).collect();
+    let bounds: Vec<Option<String>> = class.fields.iter().zip(&field_types)
+        .map(|(field, ty)| {
+            field.ctf_type.lazy_text_format()
+                .map(|(_, bound)| format!("{}: {}::{}", ty, options.std_crate(), bound))
+        })
+        .collect();
+    let params: Vec<String> = (0..class.fields.len()).map(|i| format!("F{}", i)).collect();
+    let unset: Vec<&str> = class.fields.iter().map(|_| "super::Unset").collect();
+
+    let mut out = String::new();
+    out += &format!(r"
+        /// Builder for the `{}:{}` tracepoint, see [`{}`]
+        pub(in super::super) struct {}{} {{
+", provider.name, name, name, type_name, generics(&params));
+    for (member, param) in members.iter().zip(&params) {
+        out += &format!("            {}: {},\n", member, param);
+    }
+    out += "        }\n";
+
+    let init: Vec<String> = members.iter().map(|m| format!("{}: super::Unset", m)).collect();
+    out += &format!(r"
+        /// Start building a `{}:{}` event. It is only recorded once every field has been set
+        /// and `emit` is called.
+        {}#[inline(always)]
+        pub(in super::super) fn {}() -> {}{} {{
+            {} {{ {} }}
+        }}
+", provider.name, name, separated(instance_docs(class, instance, false, "        "), "        "),
+            name, type_name, generics(&unset), type_name, init.join(", "));
+
+    out += &format!(r"
+        impl{0} {1}{0} {{
+            /// Whether any tracing session is recording this event
+            #[inline(always)]
+            pub fn enabled(&self) -> bool {{
+", generics(&params), type_name);
+    if options.is_compiled_out(instance) {
+        out += "                false\n";
+    } else {
+        let state = state_word(provider, class, instance, options);
+        out += &format!("                unsafe {{ {} != 0 }}\n", state);
+    }
+    out += "            }\n";
+    for (i, field) in class.fields.iter().enumerate() {
+        let mut result = params.clone();
+        result[i] = field_types[i].clone();
+        let moved: Vec<String> = members.iter().enumerate()
+            .map(|(j, m)| if i == j { format!("{}: value", m) } else { format!("{0}: self.{0}", m) })
+            .collect();
+        let method_params = if borrows[i] {
+            "<'a>".to_string()
+        } else if let Some(ref bound) = bounds[i] {
+            format!("<{}>", bound)
+        } else {
+            String::new()
+        };
+        let doc = field_doc(field).map(|doc| format!("/// {}\n            ", doc)).unwrap_or_default();
+        out += &format!(r"
+            {6}#[inline(always)]
+            pub fn {0}{1}(self, value: {2}) -> {3}{4} {{
+                {3} {{ {5} }}
+            }}
+", setter_name(&field.name), method_params, field_types[i], type_name, generics(&result), moved.join(", "), doc);
+    }
+    out += "        }\n";
+
+    let mut impl_params: Vec<String> = bounds.into_iter().flatten().collect();
+    if borrows.contains(&true) {
+        impl_params.insert(0, "'a".into());
+    }
+    out += &format!(r"
+        impl{} {}{} {{
+            /// Record the event
+            #[inline(always)]
+            {}pub fn emit(self) {{
+", generics(&impl_params), type_name, generics(&field_types), track_caller(class, "            "));
+    if !options.is_compiled_out(instance) {
+        let values: Vec<String> = members.iter().map(|m| format!("self.{}", m)).collect();
+        out += &format!("                {}\n",
+                        generate_native_call(provider, class, instance, &values, options, "                "));
+    }
+    out += "            }\n        }\n";
+
+    out
+}
+
+/// Names the builder methods already use, and identifiers that can't even be raw
+const RESERVED_SETTERS: &[&str] = &["emit", "enabled", "self", "Self", "super", "crate", "_"];
+
+/// Keywords of every Rust edition, which setters have to spell as raw identifiers
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+    "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+    "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The first field (as `provider:class.field`) that can't be turned into a builder method
+pub(in super) fn first_unusable_setter(providers: &[Provider]) -> Option<String> {
+    providers.iter()
+        .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+        .flat_map(|(p, c)| c.fields.iter().map(move |f| (p, c, f)))
+        .find(|&(_, _, f)| RESERVED_SETTERS.contains(&&f.name[..]))
+        .map(|(p, c, f)| format!("{}:{}.{}", p.name, c.class_name, f.name))
+}
+
+fn setter_name(field_name: &str) -> String {
+    if KEYWORDS.contains(&field_name) {
+        format!("r#{}", field_name)
+    } else {
+        field_name.into()
+    }
+}
+
+/// `docs` following other doc comment lines, with an empty line in between
+fn separated(docs: String, indent: &str) -> String {
+    if docs.is_empty() {
+        docs
+    } else {
+        format!("///\n{}{}", indent, docs)
+    }
+}
+
+fn generics<S: AsRef<str>>(params: &[S]) -> String {
+    if params.is_empty() {
+        String::new()
+    } else {
+        let params: Vec<&str> = params.iter().map(|p| p.as_ref()).collect();
+        format!("<{}>", params.join(", "))
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars)
+        })
+        .collect()
+}
+
+/// `lifetime` is put on every reference in the type, and is either empty or e.g. `'a `
+fn rust_param_type(field: &Field, std_crate: &str, lifetime: &str) -> String {
+    if let Some(ref rust_type) = field.rust_type {
+        return format!("&{}{}", lifetime, rust_type);
+    }
+    let ty = rust_type_for(&field.ctf_type, std_crate, lifetime);
+    if field.optional {
+        format!("Option<{}>", ty)
+    } else {
+        ty
+    }
+}
+
+/// What gets recorded in place of an absent optional field
+fn zero_value(ty: &CTFType, std_crate: &str) -> String {
+    use CTFType::*;
+    match *ty {
+        Integer(_) |
+        IntegerNoWrite(_) |
+        IntegerHex(_) |
+        IntegerNetwork(_) |
+        IntegerNetworkHex(_) |
+        U128 |
+        I128 => "0".into(),
+
+        Float(_) |
+        FloatNoWrite(_) => "0.0".into(),
+
+        Array(_, l) |
+        ArrayNoWrite(_, l) |
+        ArrayText(l) |
+        ArrayTextNoWrite(l) => format!("&[0; {}]", l),
+
+        // An unsized slice, so both `match` arms have the same type
+        Sequence(_) |
+        SequenceNoWrite(_) => "&[][..]".into(),
+
+        SequenceText |
+        SequenceTextNoWrite => "\"\"".into(),
+
+        String |
+        StringNoWrite => "Default::default()".into(),
+
+        Bool => "false".into(),
+        Char => "'\\0'".into(),
+        Pointer => format!("{}::ptr::null()", std_crate),
+        Duration => format!("{}::time::Duration::new(0, 0)", std_crate),
+        Ipv4Addr => format!("{}::net::Ipv4Addr::new(0, 0, 0, 0)", std_crate),
+        Ipv6Addr => format!("{}::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)", std_crate),
+        IpAddr => format!("{0}::net::IpAddr::V6({0}::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0))", std_crate),
+        SocketAddr => format!("{0}::net::SocketAddr::new({0}::net::IpAddr::V6({0}::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0)), 0)",
+                              std_crate),
+        Path => "::std::path::Path::new(\"\")".into(),
+        OsStr => "::std::ffi::OsStr::new(\"\")".into(),
+
+        Display |
+        Debug |
+        Serde(_) |
+        Enum |
+        EnumNoWrite => unimplemented!(),
+    }
+}
+
+fn rust_type_for(ty: &CTFType, std_crate: &str, lifetime: &str) -> String {
     use CTFType::*;
     match *ty {
         Integer(i) |
@@ -78,32 +662,134 @@ fn rust_type_for(ty: &CTFType) -> String {
         FloatNoWrite(f) => f.rust_type().into(),
 
         String |
-        StringNoWrite => "::std::ffi::CStr".into(),
+        StringNoWrite => format!("&{}{}::ffi::CStr", lifetime, std_crate),
 
         Array(i, l) |
-        ArrayNoWrite(i, l) => format!("&[{}; {}]", i.rust_type(), l),
+        ArrayNoWrite(i, l) => format!("&{}[{}; {}]", lifetime, i.rust_type(), l),
 
-        ArrayText(_) => "&str".into(),
+        ArrayText(l) |
+        ArrayTextNoWrite(l) => format!("&{}[u8; {}]", lifetime, l),
 
         Sequence(i) |
-        SequenceNoWrite(i) => format!("&[{}]", i.rust_type()),
+        SequenceNoWrite(i) => format!("&{}[{}]", lifetime, i.rust_type()),
 
         SequenceText |
-        SequenceTextNoWrite => "&str".into(),
+        SequenceTextNoWrite => format!("&{}str", lifetime),
+
+        Display => format!("impl {}::fmt::Display", std_crate),
+        Debug => format!("impl {}::fmt::Debug", std_crate),
+
+        Bool => "bool".into(),
+        Char => "char".into(),
+        Pointer => format!("*const {}::ffi::c_void", std_crate),
+        U128 => "u128".into(),
+        I128 => "i128".into(),
+        Duration => format!("{}::time::Duration", std_crate),
+        Ipv4Addr => format!("{}::net::Ipv4Addr", std_crate),
+        Ipv6Addr => format!("{}::net::Ipv6Addr", std_crate),
+        IpAddr => format!("{}::net::IpAddr", std_crate),
+        SocketAddr => format!("{}::net::SocketAddr", std_crate),
+        // Only unix has a byte representation for these, which is fine for lttng
+        Path => format!("&{}::std::path::Path", lifetime),
+        OsStr => format!("&{}::std::ffi::OsStr", lifetime),
+
+        Serde(_) => unreachable!("serde fields take their own Rust type"),
 
         Enum |
         EnumNoWrite => unimplemented!(),
     }
 }
 
-fn c_arg_for_field(base_name: String, field: &Field) -> String {
-    if let CTFType::SequenceText = field.ctf_type {
-        format!("::std::mem::transmute({0}.as_bytes().as_ptr()), {0}.len()", base_name)
-    } else if field.ctf_type.is_sequence() {
-        base_name.clone() + ", " + &base_name + ".len()"
-    } else if let CTFType::Array(_, _) | CTFType::ArrayNoWrite(_, _) = field.ctf_type {
-        format!("{}.as_ptr()", base_name)
-    } else {
-        base_name
+/// The first field (as `provider:class.field`) whose Rust type only exists in `std`
+pub(in super) fn first_std_only_field(providers: &[Provider]) -> Option<String> {
+    providers.iter()
+        .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+        .flat_map(|(p, c)| c.fields.iter().map(move |f| (p, c, f)))
+        .find(|&(_, _, f)| matches!(f.ctf_type, CTFType::Path | CTFType::OsStr | CTFType::Serde(_)))
+        .map(|(p, c, f)| format!("{}:{}.{}", p.name, c.class_name, f.name))
+}
+
+/// The first optional field (as `provider:class.field`) that can't be optional
+pub(in super) fn first_unsupported_optional(providers: &[Provider]) -> Option<String> {
+    providers.iter()
+        .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+        .flat_map(|(p, c)| c.fields.iter().map(move |f| (p, c, f)))
+        .find(|&(_, _, f)| {
+            f.optional && matches!(f.ctf_type, CTFType::Display | CTFType::Debug | CTFType::Serde(_))
+        })
+        .map(|(p, c, f)| format!("{}:{}.{}", p.name, c.class_name, f.name))
+}
+
+/// The first serde field (as `provider:class.field`) that doesn't know its Rust type
+pub(in super) fn first_untyped_serde_field(providers: &[Provider]) -> Option<String> {
+    providers.iter()
+        .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+        .flat_map(|(p, c)| c.fields.iter().map(move |f| (p, c, f)))
+        .find(|&(_, _, f)| f.rust_type.is_none() && matches!(f.ctf_type, CTFType::Serde(_)))
+        .map(|(p, c, f)| format!("{}:{}.{}", p.name, c.class_name, f.name))
+}
+
+/// The octets of `ip`, an `IpAddr`, with IPv4 addresses mapped into IPv6
+fn ip_addr_octets(ip: &str, std_crate: &str) -> String {
+    format!("match {0} {{ {1}::net::IpAddr::V4(ip) => ip.to_ipv6_mapped(), {1}::net::IpAddr::V6(ip) => ip }}\
+             .octets().as_ptr()", ip, std_crate)
+}
+
+fn os_str_arg(os_str: &str) -> String {
+    format!("::std::os::unix::ffi::OsStrExt::as_bytes({0}).as_ptr() as *const _, \
+             ::std::os::unix::ffi::OsStrExt::as_bytes({0}).len()", os_str)
+}
+
+fn c_arg_for_field(base_name: String, field: &Field, std_crate: &str) -> String {
+    use CTFType::*;
+    match field.ctf_type {
+        // Serde fields have been encoded into a `&[u8]` by now
+        Serde(_) => format!("{0}.as_ptr(), {0}.len()", base_name),
+
+        // C wants `const char *`, which is `i8` or `u8` depending on the target
+        // Display and Debug fields have been formatted into a `&str` by now
+        SequenceText |
+        SequenceTextNoWrite |
+        Display |
+        Debug => format!("{0}.as_bytes().as_ptr() as *const _, {0}.len()", base_name),
+        ArrayText(_) |
+        ArrayTextNoWrite(_) => format!("{}.as_ptr() as *const _", base_name),
+
+        // bindgen maps `size_t` and `intptr_t` to whatever they are on the target
+        Sequence(i) |
+        SequenceNoWrite(i) if i.is_pointer_sized() =>
+            format!("{0}.as_ptr() as *const _, {0}.len()", base_name),
+        Sequence(_) |
+        SequenceNoWrite(_) => format!("{0}.as_ptr(), {0}.len()", base_name),
+
+        Array(i, _) |
+        ArrayNoWrite(i, _) if i.is_pointer_sized() => format!("{}.as_ptr() as *const _", base_name),
+        Integer(i) |
+        IntegerNoWrite(i) |
+        IntegerHex(i) |
+        IntegerNetwork(i) |
+        IntegerNetworkHex(i) if i.is_pointer_sized() => format!("{} as _", base_name),
+
+        String |
+        StringNoWrite |
+        Array(_, _) |
+        ArrayNoWrite(_, _) => format!("{}.as_ptr()", base_name),
+
+        Bool => format!("{} as u8", base_name),
+        Char => format!("{} as u32", base_name),
+        Pointer => format!("{} as usize as _", base_name),
+        U128 => format!("({0} >> 64) as u64, {0} as u64", base_name),
+        I128 => format!("({0} >> 64) as i64, {0} as u64", base_name),
+        Duration => format!(
+            "{0}.as_secs().saturating_mul(1_000_000_000).saturating_add(u64::from({0}.subsec_nanos()))",
+            base_name),
+        Ipv4Addr |
+        Ipv6Addr => format!("{}.octets().as_ptr()", base_name),
+        IpAddr => ip_addr_octets(&base_name, std_crate),
+        SocketAddr => format!("{}, {}.port()", ip_addr_octets(&format!("{}.ip()", base_name), std_crate), base_name),
+        Path => os_str_arg(&format!("{}.as_os_str()", base_name)),
+        OsStr => os_str_arg(&base_name),
+
+        _ => base_name,
     }
 }
@@ -0,0 +1,48 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The complete set of files produced by a [`Generator`](::Generator), held in memory.
+///
+/// Paths are relative to the output directory, unless an absolute
+/// [`output_file_name`](::Generator::output_file_name) was requested.
+/// This is what gets vendored when generating sources outside of a build script.
+pub struct GeneratedSources {
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl GeneratedSources {
+    pub(in super) fn new() -> Self {
+        GeneratedSources {
+            files: Vec::new(),
+        }
+    }
+
+    pub(in super) fn add<P: Into<PathBuf>>(&mut self, path: P, contents: Vec<u8>) {
+        self.files.push((path.into(), contents));
+    }
+
+    /// Iterate over the generated files as `(path, contents)` pairs.
+    pub fn files(&self) -> impl Iterator<Item=(&Path, &[u8])> {
+        self.files.iter().map(|(path, contents)| (path.as_path(), &contents[..]))
+    }
+
+    /// Look up the contents of a single generated file
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&[u8]> {
+        self.files.iter()
+            .find(|(p, _)| p == path.as_ref())
+            .map(|(_, contents)| &contents[..])
+    }
+
+    /// Write all generated files into `dir`, creating directories as needed.
+    pub fn write_to<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        for (path, contents) in &self.files {
+            let path = dir.as_ref().join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use cc;
+
+/// How the final binary should link against `liblttng-ust`
+#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+pub enum LinkMode {
+    /// Link against the shared `liblttng-ust.so`. This is the default, unless `lttng-ust-sys`
+    /// links the static library.
+    Dynamic,
+    /// Link against the static `liblttng-ust.a`.
+    /// The archive's own dependencies (`liburcu-bp`, `libdl`, etc.) still need to be
+    /// discoverable by the linker.
+    Static,
+}
+
+impl LinkMode {
+    pub(in super) fn cargo_kind(&self) -> &'static str {
+        match *self {
+            LinkMode::Dynamic => "dylib",
+            LinkMode::Static => "static",
+        }
+    }
+}
+
+/// How `lttng-ust-sys` links `liblttng-ust`, so the tracepoint library doesn't link it a
+/// second time differently. It publishes that as `DEP_LTTNG_UST_STATIC` to the crates that
+/// depend on it directly; the others get what it decided from, `LTTNG_UST_STATIC`.
+pub(in super) fn lttng_ust_sys_link_mode() -> LinkMode {
+    link_mode_from(env::var_os("DEP_LTTNG_UST_STATIC"), env::var_os("LTTNG_UST_STATIC"))
+}
+
+pub(in super) fn link_mode_from(metadata: Option<OsString>, variable: Option<OsString>) -> LinkMode {
+    match metadata.or(variable) {
+        Some(ref value) if value != "0" => LinkMode::Static,
+        _ => LinkMode::Dynamic,
+    }
+}
+
+/// The target triple we're generating for.
+/// Prefers an explicit override, then whatever cargo told the build script.
+pub(in super) fn target_triple(explicit: &Option<String>) -> Option<String> {
+    explicit.clone().or_else(|| env::var("TARGET").ok())
+}
+
+/// Whether or not we're building for something other than the machine running the generator
+pub(in super) fn is_cross_compiling(target: &Option<String>) -> bool {
+    match (target, env::var("HOST")) {
+        (Some(target), Ok(host)) => *target != host,
+        _ => false,
+    }
+}
+
+/// The version of the Rust compiler cargo builds the crate with, as `(major, minor)`.
+/// `None` outside of build scripts, or if it can't be run.
+pub(in super) fn rustc_version() -> Option<(u32, u32)> {
+    let output = Command::new(env::var_os("RUSTC")?)
+        .arg("--version")
+        .output()
+        .ok()?;
+    parse_rustc_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the output of `rustc --version`, e.g. `rustc 1.82.0 (f6e511eec 2024-10-15)`
+fn parse_rustc_version(version: &str) -> Option<(u32, u32)> {
+    let mut numbers = version.split_whitespace().nth(1)?.split('.');
+    Some((numbers.next()?.parse().ok()?, numbers.next()?.parse().ok()?))
+}
+
+/// Operating systems whose binaries are ELF, and run the functions in `.init_array` at startup
+const ELF_SYSTEMS: &[&str] = &[
+    "linux", "android", "freebsd", "netbsd", "openbsd", "dragonfly", "solaris", "illumos",
+    "fuchsia", "haiku", "hurd", "redox",
+];
+
+/// The lttng-ust version declared by the `lttng/ust-version.h` among `headers`, as
+/// `(major, minor)`
+pub(in super) fn lttng_ust_version(headers: &[PathBuf]) -> Option<(u32, u32)> {
+    let header = headers.iter().find(|path| path.ends_with("lttng/ust-version.h"))?;
+    parse_lttng_ust_version(&fs::read_to_string(header).ok()?)
+}
+
+fn parse_lttng_ust_version(header: &str) -> Option<(u32, u32)> {
+    let define = |name: &str| header.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|words| words.len() == 3 && words[0] == "#define" && words[1] == name)
+        .and_then(|words| words[2].parse().ok());
+    Some((define("LTTNG_UST_MAJOR_VERSION")?, define("LTTNG_UST_MINOR_VERSION")?))
+}
+
+/// Whether `target` has ELF `.init_array` sections, going by the OS part of its triple
+pub(in super) fn has_init_array(target: &str) -> bool {
+    target.split('-').skip(1).any(|part| ELF_SYSTEMS.contains(&part))
+}
+
+/// Some architectures are spelled differently by rustc and clang.
+pub(in super) fn clang_target(rust_target: &str) -> String {
+    if let Some(rest) = rust_target.strip_prefix("riscv64gc-") {
+        format!("riscv64-{}", rest)
+    } else if let Some(rest) = rust_target.strip_prefix("riscv32gc-") {
+        format!("riscv32-{}", rest)
+    } else {
+        rust_target.into()
+    }
+}
+
+/// Ask a (GCC-like) cross compiler where its sysroot lives.
+/// libclang doesn't know about the sysroot baked into `aarch64-linux-gnu-gcc` and friends,
+/// so without this bindgen can't find `stdint.h` for the target.
+pub(in super) fn probe_sysroot(compiler: &cc::Tool) -> Option<PathBuf> {
+    let output = Command::new(compiler.path())
+        .arg("-print-sysroot")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sysroot = String::from_utf8(output.stdout).ok()?;
+    let sysroot = PathBuf::from(sysroot.trim());
+    if sysroot.as_os_str().is_empty() || !sysroot.is_dir() {
+        None
+    } else {
+        Some(sysroot)
+    }
+}
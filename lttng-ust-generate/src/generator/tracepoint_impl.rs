@@ -1,32 +1,48 @@
-use std::fs::File;
 use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
 
 use ::{CTFType, EventClass, Field, Provider};
 use super::ctf_field_c_type;
+use super::redaction::{recorded_fields, RedactionPolicy};
 
-pub(in super) fn generate_tp_impl(path: &PathBuf, include_path: &PathBuf) -> io::Result<()> {
-    let mut outf = File::create(path)
-        .expect(&format!("Failed to create tracepoint impl {:?}\n", path));
+/// How many arguments `TP_ARGS` takes at most. Its macros are only defined up to there.
+const MAX_TP_ARGS: usize = 10;
+
+/// The first event class (as `provider:class`) whose tracepoints take more arguments than
+/// lttng-ust allows. Sequences take two, and the fields added behind the user's back (common
+/// fields, presence flags, split values and the caller location) count too.
+pub(in super) fn first_overlong_class(providers: &[Provider]) -> Option<String> {
+    providers.iter()
+        .flat_map(|p| p.classes.iter().map(move |c| (p, c)))
+        .find(|&(_, c)| {
+            c.c_fields().iter().map(|f| if f.ctf_type.is_sequence() { 2 } else { 1 }).sum::<usize>() > MAX_TP_ARGS
+        })
+        .map(|(p, c)| format!("{}:{}", p.name, c.class_name))
+}
+
+#[allow(clippy::write_with_newline)]
+pub(in super) fn generate_tp_impl<F: Write>(outf: &mut F, include_name: &str) -> io::Result<()> {
     write!(outf, "#define TRACEPOINT_CREATE_PROBES\n")?;
     write!(outf, "#define TRACEPOINT_DEFINE\n")?;
-    write!(outf, "#include \"{}\"\n", include_path.to_string_lossy())
+    writeln!(outf, "#include \"{}\"", include_name)
 }
 
-pub(in super) fn generate_tp_header(path: &PathBuf, providers: &[Provider]) -> io::Result<()> {
-    let mut outf = File::create(path)
-        .expect(&format!("Failed to create tracepoint header {:?}\n", path));
-
+/// `header_name` is how the header refers to itself. lttng-ust re-includes it from
+/// `lttng/tracepoint-event.h`, so it must be resolvable through the include path.
+#[allow(clippy::write_with_newline)]
+pub(in super) fn generate_tp_header<F: Write>(outf: &mut F,
+                                              providers: &[Provider],
+                                              header_name: &str,
+                                              policy: RedactionPolicy) -> io::Result<()> {
     write!(outf, "#undef TRACEPOINT_INCLUDE\n")?;
-    write!(outf, "#define TRACEPOINT_INCLUDE \"{}\"\n\n", path.to_string_lossy())?;
+    write!(outf, "#define TRACEPOINT_INCLUDE \"{}\"\n\n", header_name)?;
 
     write!(outf, "#if !defined(_RUST_TRACEPOINT_GUARD)")?;
     write!(outf, " || defined(TRACEPOINT_HEADER_MULTI_READ)\n")?;
 
     write!(outf, "#define _RUST_TRACEPOINT_GUARD\n\n")?;
     for provider in providers {
-        generate_provider(provider, &mut outf)?;
+        generate_provider(provider, policy, outf)?;
     }
     write!(outf, "#endif\n")?;
     write!(outf, "#include <lttng/tracepoint-event.h>\n")?;
@@ -34,7 +50,8 @@ pub(in super) fn generate_tp_header(path: &PathBuf, providers: &[Provider]) -> i
     Ok(())
 }
 
-fn generate_provider<F: Write>(provider: &Provider, outf: &mut F) -> io::Result<()> {
+#[allow(clippy::write_with_newline)]
+fn generate_provider<F: Write>(provider: &Provider, policy: RedactionPolicy, outf: &mut F) -> io::Result<()> {
     write!(outf, "#undef TRACEPOINT_PROVIDER\n")?;
     write!(outf, "#define TRACEPOINT_PROVIDER {}\n\n", provider.name)?;
     write!(outf, "#include <lttng/tracepoint.h>\n\n")?;
@@ -44,7 +61,8 @@ fn generate_provider<F: Write>(provider: &Provider, outf: &mut F) -> io::Result<
     for event_class in &provider.classes {
         write!(outf, "TRACEPOINT_EVENT_CLASS(\n")?;
         write!(outf, "    {},\n", provider.name)?;
-        generate_event_class(event_class, outf)?;
+        let fields = recorded_fields(event_class.c_fields(), policy);
+        generate_event_class(event_class, &fields, outf)?;
 
         write!(outf, "/**--== {} instances ==--**/\n", event_class.class_name)?;
         for instance in &event_class.instances {
@@ -52,23 +70,28 @@ fn generate_provider<F: Write>(provider: &Provider, outf: &mut F) -> io::Result<
             write!(outf, "    {},\n", provider.name)?;
             write!(outf, "    {},\n", event_class.class_name)?;
             write!(outf, "    {},\n", instance.name)?;
-            generate_tp_args(&event_class.fields, outf)?;
+            generate_tp_args(&fields, outf)?;
             write!(outf, "\n)\n")?;
             // TODO: emit TRACEPOINT_LOGLEVEL
             write!(outf, "TRACEPOINT_LOGLEVEL({}, {}, {})\n\n",
                    provider.name, instance.name, instance.level.lttng_level())?;
+            if let Some(ref uri) = instance.emf_uri {
+                write!(outf, "TRACEPOINT_MODEL_EMF_URI({}, {}, {})\n\n",
+                       provider.name, instance.name, c_string_literal(uri))?;
+            }
         }
     }
 
     Ok(())
 }
 
-fn generate_event_class<F: Write>(event_class: &EventClass, outf: &mut F) -> io::Result<()> {
+#[allow(clippy::write_with_newline)]
+fn generate_event_class<F: Write>(event_class: &EventClass, fields: &[Field], outf: &mut F) -> io::Result<()> {
     write!(outf, "    {},\n", event_class.class_name)?;
-    generate_tp_args(&event_class.fields, outf)?;
+    generate_tp_args(fields, outf)?;
     write!(outf, ",\n    TP_FIELDS(\n")?;
     let mut first = true;
-    for field in &event_class.fields {
+    for field in fields {
         if first {
             first = false;
         } else {
@@ -83,7 +106,12 @@ fn generate_event_class<F: Write>(event_class: &EventClass, outf: &mut F) -> io:
     Ok(())
 }
 
+#[allow(clippy::write_with_newline)]
 fn generate_tp_args<F: Write>(fields: &[Field], outf: &mut F) -> io::Result<()> {
+    if fields.is_empty() {
+        // lttng-ust needs an explicit `void` for tracepoints without arguments
+        return write!(outf, "    TP_ARGS(void)");
+    }
     write!(outf, "    TP_ARGS(\n")?;
     let mut first = true;
     for field in fields {
@@ -126,17 +154,36 @@ fn generate_ctf_call<F: Write>(field: &Field, outf: &mut F) -> io::Result<()> {
         CTFType::Array(i, l) =>
             write!(outf, "ctf_array({0}, {1}, {1}_arg, {2})", i.c_type(), field.name, l),
         CTFType::ArrayText(l) =>
-            write!(outf, "ctf_array_text(text, {0}, {0}_arg, {1})", field.name, l),
+            write!(outf, "ctf_array_text(char, {0}, {0}_arg, {1})", field.name, l),
+        CTFType::ArrayTextNoWrite(l) =>
+            write!(outf, "ctf_array_text_nowrite(char, {0}, {0}_arg, {1})", field.name, l),
         CTFType::ArrayNoWrite(i, l) =>
             write!(outf, "ctf_array_nowrite({0}, {1}, {1}_arg, {2})", i.c_type(), field.name, l),
         CTFType::Sequence(i) =>
             write!(outf, "ctf_sequence({0}, {1}, {1}_arg, size_t, {1}_len)", i.c_type(), field.name),
         CTFType::SequenceNoWrite(i) =>
-            write!(outf, "ctf_sequence({0}, {1}, {1}_arg, size_t, {1}_len)", i.c_type(), field.name),
-        CTFType::SequenceText =>
+            write!(outf, "ctf_sequence_nowrite({0}, {1}, {1}_arg, size_t, {1}_len)", i.c_type(), field.name),
+        CTFType::SequenceText | CTFType::Display | CTFType::Debug =>
             write!(outf, "ctf_sequence_text(char, {0}, {0}_arg, size_t, {0}_len)", field.name),
         CTFType::SequenceTextNoWrite =>
             write!(outf, "ctf_sequence_text_nowrite(char, {0}, {0}_arg, size_t, {0}_len)", field.name),
         CTFType::Enum | CTFType::EnumNoWrite => unimplemented!(),
+        ty => unreachable!("{:?} is lowered by EventClass::c_fields", ty),
+    }
+}
+
+fn c_string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            '\n' => literal.push_str("\\n"),
+            c => literal.push(c),
+        }
     }
+    literal.push('"');
+    literal
 }
@@ -1,52 +1,85 @@
-use std::fs::File;
 use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
 use bindgen::Builder;
 
-use ::{EventClass, EventInstance, Field, Provider};
+use ::{CTFType, EventClass, EventInstance, Field, Provider};
 use super::ctf_field_c_type;
-
-pub(in super) fn generate_interface_impl(path: &PathBuf,
-                                         providers: &[Provider],
-                                         interface_header: &PathBuf,
-                                         tracepoint_header: &PathBuf) -> io::Result<()> {
-    let mut outf = File::create(path)
-        .expect(&format!("Failed to create tracepoint interface implementation {:?}\n", path));
-
-    write!(outf, "#include \"{}\"\n", interface_header.to_string_lossy())?;
-    write!(outf, "#include \"{}\"\n", tracepoint_header.to_string_lossy())?;
+use super::redaction::{hashed_arg, needs_hash, write_hash_function, RedactionPolicy};
+
+pub(in super) fn generate_interface_impl<F: Write>(outf: &mut F,
+                                                   providers: &[Provider],
+                                                   interface_header: &str,
+                                                   tracepoint_header: &str,
+                                                   policy: RedactionPolicy) -> io::Result<()> {
+    writeln!(outf, "#include \"{}\"", interface_header)?;
+    writeln!(outf, "#include \"{}\"", tracepoint_header)?;
+    if needs_hash(providers, policy) {
+        write_hash_function(outf, policy)?;
+    }
 
     for provider in providers {
-        generate_provider_impl(provider, &mut outf)?;
+        generate_provider_impl(provider, policy, outf)?;
     }
 
     Ok(())
 }
 
-pub(in super) fn generate_interface_header(path: &PathBuf, providers: &[Provider]) -> io::Result<()> {
-    let mut outf = File::create(path)
-        .expect(&format!("Failed to create tracepoint interface header {:?}\n", path));
-
-    write!(outf, "#if !defined(_RUST_TRACEPOINT_INTERFACE)\n")?;
-    write!(outf, "#define _RUST_TRACEPOINT_INTERFACE\n")?;
-    write!(outf, "#include <stdint.h>\n")?;
-    write!(outf, "#include <stddef.h>\n")?;
+/// The interface header doubles as the public header for C and C++ code that wants to fire
+/// the same tracepoints, so it has to be self-contained and documented.
+pub(in super) fn generate_interface_header<F: Write>(outf: &mut F,
+                                                     providers: &[Provider],
+                                                     lib_name: &str) -> io::Result<()> {
+    let guard = header_guard(lib_name);
+    writeln!(outf, "/* Generated by lttng-ust-generate. Do not edit. */")?;
+    writeln!(outf, "#if !defined({})", guard)?;
+    writeln!(outf, "#define {}", guard)?;
+    writeln!(outf, "#include <stdint.h>")?;
+    writeln!(outf, "#include <stddef.h>")?;
+    writeln!(outf)?;
+    writeln!(outf, "#ifdef __cplusplus")?;
+    writeln!(outf, "extern \"C\" {{")?;
+    writeln!(outf, "#endif")?;
 
     for provider in providers {
-        generate_provider_header(provider, &mut outf)?;
+        generate_provider_header(provider, outf)?;
     }
 
+    writeln!(outf)?;
+    writeln!(outf, "#ifdef __cplusplus")?;
+    writeln!(outf, "}}")?;
+    writeln!(outf, "#endif")?;
+    writeln!(outf)?;
     write!(outf, "#endif")?;
     Ok(())
 }
 
-pub(in super) fn whitelist_interface(providers: &[Provider], mut b: Builder) -> Builder {
+/// The lttng-ust ABI the tracepoint structs are declared with, which decides their names
+#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+pub(in super) enum UstAbi {
+    /// lttng-ust 2.12 and earlier
+    V2_12,
+    /// lttng-ust 2.13 and later
+    V2_13,
+}
+
+impl UstAbi {
+    /// The ABI of the lttng-ust `(major, minor)` version
+    pub(in super) fn from_version(version: (u32, u32)) -> Self {
+        if version >= (2, 13) { UstAbi::V2_13 } else { UstAbi::V2_12 }
+    }
+}
+
+/// `abi` is `None` when the tracepoint structs aren't declared for bindgen
+pub(in super) fn whitelist_interface(providers: &[Provider], abi: Option<UstAbi>, mut b: Builder) -> Builder {
     for provider in providers {
         for event_class in &provider.classes {
             for instance in &event_class.instances {
                 let fname = generate_func_name(provider, event_class, instance);
                 eprintln!("whitelisting: {}", fname);
+                b = b.allowlist_function(generate_enabled_func_name(&fname));
+                if let Some(abi) = abi {
+                    b = b.allowlist_var(generate_tracepoint_var_name(provider, instance, abi));
+                }
                 b = b.allowlist_function(fname);
             }
         }
@@ -54,15 +87,28 @@ pub(in super) fn whitelist_interface(providers: &[Provider], mut b: Builder) ->
     b
 }
 
-fn generate_provider_impl<F: Write>(provider: &Provider, outf: &mut F) -> io::Result<()> {
+fn generate_provider_impl<F: Write>(provider: &Provider, policy: RedactionPolicy, outf: &mut F) -> io::Result<()> {
     for event_class in &provider.classes {
         for instance in &event_class.instances {
-            write!(outf, "void {}(", generate_func_name(provider, event_class, instance))?;
-            generate_c_args(&event_class.fields, outf, true)?;
-            write!(outf, ") {{\n")?;
-            write!(outf, "    tracepoint({}, {}, ", provider.name, instance.name)?;
-            generate_c_args(&event_class.fields, outf, false)?;
-            write!(outf, ");\n")?;
+            let fname = generate_func_name(provider, event_class, instance);
+            write!(outf, "void {}(", fname)?;
+            generate_c_args(&event_class.c_fields(), outf, true)?;
+            writeln!(outf, ") {{")?;
+            write!(outf, "    tracepoint({}, {}", provider.name, instance.name)?;
+            let hash = matches!(policy, RedactionPolicy::KeyedHash(_));
+            for field in &event_class.c_fields() {
+                write!(outf, ", ")?;
+                if hash && field.sensitive {
+                    write!(outf, "{}", hashed_arg(field))?;
+                } else {
+                    generate_c_args(std::slice::from_ref(field), outf, false)?;
+                }
+            }
+            writeln!(outf, ");")?;
+            write!(outf, "}}\n\n")?;
+
+            writeln!(outf, "int {}(void) {{", generate_enabled_func_name(&fname))?;
+            writeln!(outf, "    return tracepoint_enabled({}, {});", provider.name, instance.name)?;
             write!(outf, "}}\n\n")?;
         }
     }
@@ -73,15 +119,77 @@ fn generate_provider_impl<F: Write>(provider: &Provider, outf: &mut F) -> io::Re
 fn generate_provider_header<F: Write>(provider: &Provider, outf: &mut F) -> io::Result<()> {
     for event_class in &provider.classes {
         for instance in &event_class.instances {
-            write!(outf, "extern void {}(", generate_func_name(provider, event_class, instance))?;
-            generate_c_args(&event_class.fields, outf, true)?;
-            write!(outf, ");\n")?;
+            writeln!(outf)?;
+            writeln!(outf, "/**")?;
+            writeln!(outf, " * Fires the `{}:{}` tracepoint (class `{}`, level {}).",
+                     provider.name, instance.name, event_class.class_name, instance.level.lttng_level())?;
+            for description in event_class.description.iter().chain(&instance.description) {
+                writeln!(outf, " *")?;
+                for line in description.lines() {
+                    writeln!(outf, " * {}", comment_safe(line))?;
+                }
+            }
+            let fields = event_class.c_fields();
+            if !fields.is_empty() {
+                writeln!(outf, " *")?;
+            }
+            for field in &fields {
+                generate_param_doc(field, outf)?;
+            }
+            writeln!(outf, " */")?;
+            let fname = generate_func_name(provider, event_class, instance);
+            write!(outf, "extern void {}(", fname)?;
+            generate_c_args(&event_class.c_fields(), outf, true)?;
+            writeln!(outf, ");")?;
+            writeln!(outf)?;
+            writeln!(outf, "/**")?;
+            writeln!(outf, " * Whether the `{}:{}` tracepoint is enabled in any tracing session.",
+                     provider.name, instance.name)?;
+            writeln!(outf, " * Useful to skip computing expensive arguments.")?;
+            writeln!(outf, " */")?;
+            writeln!(outf, "extern int {}(void);", generate_enabled_func_name(&fname))?;
         }
     }
 
     Ok(())
 }
 
+fn generate_param_doc<F: Write>(field: &Field, outf: &mut F) -> io::Result<()> {
+    let name = &field.name;
+    let mut annotation = String::new();
+    if let Some(ref unit) = field.unit {
+        annotation += &format!(", in {}", unit);
+    }
+    if let Some(ref description) = field.description {
+        annotation += &format!(": {}", description.lines().collect::<Vec<_>>().join(" "));
+    }
+    let annotation = comment_safe(&annotation);
+    match field.ctf_type {
+        CTFType::String | CTFType::StringNoWrite =>
+            writeln!(outf, " * @param {0}_arg NUL-terminated string recorded as `{0}`{1}", name, annotation),
+        CTFType::Array(_, len) | CTFType::ArrayNoWrite(_, len) |
+        CTFType::ArrayText(len) | CTFType::ArrayTextNoWrite(len) =>
+            writeln!(outf, " * @param {0}_arg exactly {1} elements, recorded as `{0}`{2}", name, len, annotation),
+        ty if ty.is_sequence() => {
+            writeln!(outf, " * @param {0}_arg {0}_len elements, recorded as `{0}`{1}", name, annotation)?;
+            writeln!(outf, " * @param {0}_len number of elements (not bytes) in {0}_arg", name)
+        }
+        _ => writeln!(outf, " * @param {0}_arg recorded as `{0}`{1}", name, annotation),
+    }
+}
+
+/// User-provided text can't be allowed to end the comment it's in
+fn comment_safe(text: &str) -> String {
+    text.replace("*/", "* /")
+}
+
+fn header_guard(lib_name: &str) -> String {
+    let name: String = lib_name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("_{}_TRACEPOINTS_H", name)
+}
+
 pub fn generate_func_name(provider: &Provider, event_class: &EventClass, instance: &EventInstance) -> String {
     format!(
         "{}_{}_{}_tp",
@@ -91,7 +199,26 @@ pub fn generate_func_name(provider: &Provider, event_class: &EventClass, instanc
     )
 }
 
+/// The C function telling whether the tracepoint fired by `func_name` is enabled
+pub fn generate_enabled_func_name(func_name: &str) -> String {
+    format!("{}_enabled", func_name)
+}
+
+/// The tracepoint struct lttng-ust defines for `instance`, whose `state` is nonzero while it
+/// is enabled. lttng-ust 2.13 renamed it along with the rest of its API.
+pub fn generate_tracepoint_var_name(provider: &Provider, instance: &EventInstance, abi: UstAbi) -> String {
+    let prefix = match abi {
+        UstAbi::V2_12 => "__tracepoint_",
+        UstAbi::V2_13 => "lttng_ust_tracepoint_",
+    };
+    format!("{}{}___{}", prefix, provider.name, instance.name)
+}
+
 fn generate_c_args<F: Write>(fields: &[Field], outf: &mut F, include_type: bool) -> io::Result<()> {
+    if fields.is_empty() && include_type {
+        // `()` would declare a function taking unspecified arguments in C
+        return write!(outf, "void");
+    }
     let mut first = true;
     for field in fields {
         if first {
@@ -36,11 +36,20 @@
 //! many lttng-ust events registered.
 //!
 //! Second, we need to create an [event class](::EventClass). An event class describes the
-//! layout of a tracepoint event. Events can have up to 10 different fields. All field names
+//! layout of a tracepoint event. Events can have several fields, or none at all for markers
+//! like `startup_complete`, whose Rust functions take no arguments. lttng-ust passes at most
+//! 10 arguments to a tracepoint, and some fields take more than one: sequences (including
+//! `SequenceText`, `Path`, `Display`, `Debug` and `Serde` fields), `U128`, `I128` and `SocketAddr` fields
+//! take two, optional fields one more for their presence flag, and the caller location four.
+//! The common fields of the provider count too. Generation fails with
+//! [`Error::TooManyArguments`](::Error::TooManyArguments) past that. All field names
 //! should be unique within the event class. See [CTFType](::CTFType) for a list of all the
 //! types we currently support and how those types map to the `ctf_*` macros from
 //! `man 3 lttng-ust`. Also important to note is the order of the [`.add_field`](::EventClass::add_field)
 //! calls, since these determine the order of the arguments to the generated tracepoint function.
+//! Fields used by many classes can be collected in a [`FieldGroup`](::FieldGroup), and fields
+//! used by every class of a provider registered with
+//! [`Provider::add_common_fields`](::Provider::add_common_fields).
 //!
 //! Finally, we can instantiate our event class to create a specific [event](::EventInstance).
 //! This is what causes `lttng-usg-generate` to actually emit a tracepoint we can use in our code.
@@ -65,6 +74,54 @@
 //! tracepoints::my_first_rust_provider::my_first_tracepoint(42, "the meaning of life");
 //! ```
 //!
+//! ## Firing tracepoints from C and C++
+//! Every tracepoint is backed by a plain C function, declared in a generated public header
+//! named after the [generated library](::Generator::generated_lib_name). C or C++ code linked
+//! into the same binary can include that header (see
+//! [`Generator::c_include_dir`](::Generator::c_include_dir)) and emit exactly the same events
+//! as the Rust side.
+//!
+//! ## Generating sources ahead of time
+//! [`Generator::generate`](::Generator::generate) expects to run inside a build script, and
+//! needs libclang to produce the raw bindings. If your builders can't run libclang, you can
+//! instead generate everything once with [`Generator::write_sources`](::Generator::write_sources)
+//! (or the `lttng-ust-gen` binary, which reads a [schema file](::schema)), check the result
+//! in, and compile it from your `build.rs` with
+//! [`Generator::compile_pregenerated`](::Generator::compile_pregenerated):
+//!
+//! ```no_run
+//! use lttng_ust_generate::Generator;
+//!
+//! Generator::default()
+//!     .generated_lib_name("tracepoint_library_link_name")
+//!     .compile_pregenerated("vendor/tracepoints")
+//!     .expect("Unable to compile tracepoints");
+//! ```
+//!
+//! The root Rust file then lives at `vendor/tracepoints/tracepoints.rs`.
+//!
+//! ## Tracepoints across a workspace
+//! lttng-ust only lets a provider be defined once per process, and each tracepoint library
+//! brings its own copy of the probe registration code, so it's best to build exactly one of
+//! them per binary. Library crates can define their providers as usual but call
+//! [`Generator::export_providers`](::Generator::export_providers) instead of building a
+//! library of their own; they need a `links` key in their `Cargo.toml` for cargo to pass the
+//! providers on. The binary then pulls them in with
+//! [`Generator::import_dependency_providers`](::Generator::import_dependency_providers):
+//!
+//! ```no_run
+//! use lttng_ust_generate::Generator;
+//!
+//! Generator::default()
+//!     .generated_lib_name("my_app_tracepoints")
+//!     .import_dependency_providers()
+//!     .generate()
+//!     .expect("Unable to generate tracepoint bindings");
+//! ```
+//!
+//! The libraries keep their own Rust bindings, which resolve to the C functions in the
+//! binary's tracepoint library at link time.
+//!
 //! Have a look in the `examples` directory of the repository
 //! [on GitHub](https://github.com/bobtwinkles/lttng-ust-rs/tree/master/examples)
 //! for a complete usage sample.
@@ -76,14 +133,18 @@ extern crate bindgen;
 extern crate cc;
 
 mod generator;
+pub mod schema;
 
-pub use generator::Generator;
+pub use generator::{Error, GeneratedSources, Generator, LinkMode, RedactionPolicy};
 
 /// A tracepoint provider.
 /// You usually only need to create one of these
+#[derive(Clone,PartialEq,Debug)]
 pub struct Provider {
     name: String,
     classes: Vec<EventClass>,
+    /// Fields every class starts with
+    common_fields: Vec<Field>,
 }
 
 /// A lttng-ust event provider.
@@ -94,18 +155,110 @@ impl Provider {
         Provider {
             name: name.into(),
             classes: Vec::new(),
+            common_fields: Vec::new(),
         }
     }
 
     /// Create a new class of tracepoint event.
+    /// The class starts out with the provider's [common fields](::Provider::add_common_fields).
     pub fn create_class<S: Into<String>>(&mut self, class_name: S) -> &mut EventClass {
-        self.classes.push(EventClass::new(class_name.into()));
+        let mut class = EventClass::new(class_name.into());
+        class.fields = self.common_fields.clone();
+        self.classes.push(class);
         let cls_len = self.classes.len();
         &mut self.classes[cls_len - 1]
     }
+
+    /// Make every class of this provider start with the fields of `group`.
+    /// This applies to classes created both before and after the call, and the fields come
+    /// after those of earlier calls, so every generated Rust function of the provider takes
+    /// them first and in the same order.
+    pub fn add_common_fields(&mut self, group: &FieldGroup) -> &mut Self {
+        let at = self.common_fields.len();
+        for class in &mut self.classes {
+            class.fields.splice(at..at, group.fields.iter().cloned());
+        }
+        self.common_fields.extend(group.fields.iter().cloned());
+        self
+    }
+}
+
+/// A reusable list of fields, for fields shared by many event classes.
+/// Splice it into a class with [`EventClass::add_field_group`](::EventClass::add_field_group),
+/// or into every class of a provider with
+/// [`Provider::add_common_fields`](::Provider::add_common_fields).
+///
+/// ```
+/// use lttng_ust_generate::{CIntegerType, CTFType, FieldGroup, Provider};
+///
+/// let mut request = FieldGroup::new();
+/// request.add_field("request_id", CTFType::Integer(CIntegerType::U64))
+///     .add_field("tenant_id", CTFType::Integer(CIntegerType::U32));
+///
+/// let mut provider = Provider::new("my_server");
+/// provider.create_class("request_done")
+///     .add_field_group(&request)
+///     .add_field("status", CTFType::Integer(CIntegerType::U16))
+///     .instantiate("request_done");
+/// ```
+#[derive(Clone,PartialEq,Debug,Default)]
+pub struct FieldGroup {
+    fields: Vec<Field>,
+}
+
+impl FieldGroup {
+    /// Create an empty field group
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like [`EventClass::add_field`](::EventClass::add_field)
+    pub fn add_field<S: Into<String>>(&mut self, field_name: S, ty: CTFType) -> &mut Self {
+        self.fields.push(Field::new(field_name.into(), ty));
+        self
+    }
+
+    /// Like [`EventClass::add_optional_field`](::EventClass::add_optional_field)
+    pub fn add_optional_field<S: Into<String>>(&mut self, field_name: S, ty: CTFType) -> &mut Self {
+        self.fields.push(Field::optional(field_name.into(), ty));
+        self
+    }
+
+    /// Like [`EventClass::add_serde_field`](::EventClass::add_serde_field)
+    pub fn add_serde_field<S, T>(&mut self, field_name: S, encoding: SerdeEncoding, rust_type: T) -> &mut Self
+        where S: Into<String>, T: Into<String>
+    {
+        self.fields.push(Field::serde(field_name.into(), encoding, rust_type.into()));
+        self
+    }
+
+    /// Like [`EventClass::describe_field`](::EventClass::describe_field)
+    pub fn describe_field<S: Into<String>>(&mut self, field_name: &str, description: S) -> &mut Self {
+        find_field(&mut self.fields, field_name).description = Some(description.into());
+        self
+    }
+
+    /// Like [`EventClass::field_unit`](::EventClass::field_unit)
+    pub fn field_unit<S: Into<String>>(&mut self, field_name: &str, unit: S) -> &mut Self {
+        find_field(&mut self.fields, field_name).unit = Some(unit.into());
+        self
+    }
+
+    /// Like [`EventClass::mark_sensitive`](::EventClass::mark_sensitive)
+    pub fn mark_sensitive(&mut self, field_name: &str) -> &mut Self {
+        find_field(&mut self.fields, field_name).sensitive = true;
+        self
+    }
+}
+
+fn find_field<'a>(fields: &'a mut [Field], field_name: &str) -> &'a mut Field {
+    fields.iter_mut()
+        .find(|f| f.name == field_name)
+        .unwrap_or_else(|| panic!("no field named {}", field_name))
 }
 
 /// Represents a class of events that we would like to trace
+#[derive(Clone,PartialEq,Debug)]
 pub struct EventClass {
     /// The name of this class
     class_name: String,
@@ -113,6 +266,12 @@ pub struct EventClass {
     fields: Vec<Field>,
     /// The set of instances
     instances: Vec<EventInstance>,
+    /// Whether to record where in the Rust source the tracepoint was fired from
+    caller_location: bool,
+    /// What events of this class mean
+    description: Option<String>,
+    /// The fields added by `add_derived_fields`, which the derived `emit` passes to C as is
+    derived_fields: Option<Vec<Field>>,
 }
 
 /// Represents a class of tracepoints.
@@ -126,19 +285,224 @@ impl EventClass {
             class_name,
             fields: Vec::new(),
             instances: Vec::new(),
+            caller_location: false,
+            description: None,
+            derived_fields: None,
         }
     }
 
     /// Adds a new field to the tracepoint.
     /// See the [module level documentation](index.html) for examples.
     pub fn add_field<S: Into<String>>(&mut self, field_name: S, ty: CTFType) -> &mut Self {
-        self.fields.push(Field::new(
-            field_name.into(), ty
-        ));
+        self.fields.push(Field::new(field_name.into(), ty));
         // TODO: make sure field names don't conflict
         self
     }
 
+    /// Adds a new field that may be absent.
+    /// The generated Rust function takes an `Option` of the field's usual type. Two fields get
+    /// recorded: `<name>_present`, a `uint8_t` which is 1 if the value was `Some`, followed by
+    /// the value itself, zeroed (or empty) when it was `None`. Readers should only look at the
+    /// value when `<name>_present` is set, which `lttng_ust::decode` helps with. Exported schema
+    /// files name the flag in a `presence` statement after the field.
+    ///
+    /// [Display](CTFType::Display), [Debug](CTFType::Debug) and [Serde](CTFType::Serde) fields
+    /// can't be optional: generating such a class fails with
+    /// [`Error::UnsupportedOptional`](::Error::UnsupportedOptional).
+    pub fn add_optional_field<S: Into<String>>(&mut self, field_name: S, ty: CTFType) -> &mut Self {
+        self.fields.push(Field::optional(field_name.into(), ty));
+        self
+    }
+
+    /// Adds a field holding any `serde::Serialize` value of type `rust_type`, recorded as a
+    /// byte sequence in the given `encoding`.
+    /// The generated Rust function takes a `&rust_type`, which is only encoded when the
+    /// tracepoint is enabled. `rust_type` is pasted into the generated bindings as is, so it
+    /// should be an absolute path such as `crate::config::Config`.
+    ///
+    /// So that trace readers know how to decode the bytes, the field is recorded as
+    /// `<name>_<encoding>`, e.g. `config_cbor`. The Rust type can't be part of a CTF field
+    /// name; it is written to [schema files](schema/index.html) and to the runtime registry
+    /// instead.
+    pub fn add_serde_field<S, T>(&mut self, field_name: S, encoding: SerdeEncoding, rust_type: T) -> &mut Self
+        where S: Into<String>, T: Into<String>
+    {
+        self.fields.push(Field::serde(field_name.into(), encoding, rust_type.into()));
+        self
+    }
+
+    /// Adds every field of `group`, in order.
+    pub fn add_field_group(&mut self, group: &FieldGroup) -> &mut Self {
+        self.fields.extend(group.fields.iter().cloned());
+        self
+    }
+
+    /// Describe what events of this class mean.
+    /// Descriptions end up in the documentation of the generated Rust functions and C header,
+    /// and in [schema files](schema/index.html).
+    pub fn describe<S: Into<String>>(&mut self, description: S) -> &mut Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Describe what the field `field_name` holds, like [`describe`](::EventClass::describe).
+    /// Panics if the class has no such field.
+    pub fn describe_field<S: Into<String>>(&mut self, field_name: &str, description: S) -> &mut Self {
+        find_field(&mut self.fields, field_name).description = Some(description.into());
+        self
+    }
+
+    /// Set the unit of the field `field_name`, e.g. `ns` or `bytes`.
+    /// Panics if the class has no such field.
+    pub fn field_unit<S: Into<String>>(&mut self, field_name: &str, unit: S) -> &mut Self {
+        find_field(&mut self.fields, field_name).unit = Some(unit.into());
+        self
+    }
+
+    /// Mark the field `field_name` as holding sensitive data, like user identifiers.
+    /// What gets recorded for it is decided at build time by
+    /// [`Generator::redaction_policy`](::Generator::redaction_policy), without changing the
+    /// signature of the generated functions. For optional fields, only the value is redacted:
+    /// `<name>_present` is still recorded.
+    /// Panics if the class has no such field.
+    pub fn mark_sensitive(&mut self, field_name: &str) -> &mut Self {
+        find_field(&mut self.fields, field_name).sensitive = true;
+        self
+    }
+
+    /// Describe what the tracepoint `instance_name` means, on top of the class description.
+    /// Panics if the class has no such instance.
+    pub fn describe_instance<S: Into<String>>(&mut self, instance_name: &str, description: S) -> &mut Self {
+        self.find_instance(instance_name).description = Some(description.into());
+        self
+    }
+
+    /// Attach an EMF model URI to the tracepoint `instance_name`, emitted as
+    /// `TRACEPOINT_MODEL_EMF_URI`. Tools like Trace Compass use it to find out what the event
+    /// means. Panics if the class has no such instance.
+    pub fn instance_emf_uri<S: Into<String>>(&mut self, instance_name: &str, uri: S) -> &mut Self {
+        self.find_instance(instance_name).emf_uri = Some(uri.into());
+        self
+    }
+
+    /// Only record one in every `every` hits of the tracepoint `instance_name`, counting
+    /// separately on each thread. Hits are only counted while the tracepoint is enabled.
+    /// Since the share of dropped events is known, they aren't reported in the trace; the
+    /// sampling rate is recorded in [schema files](schema/index.html).
+    ///
+    /// Like [`rate_limit_instance`](::EventClass::rate_limit_instance), this is enforced by the
+    /// generated Rust functions before calling into C. Both need `std`, so
+    /// [`Generator::no_std`](::Generator::no_std) refuses them.
+    /// Panics if the class has no such instance, or if `every` is 0.
+    pub fn sample_instance(&mut self, instance_name: &str, every: u32) -> &mut Self {
+        assert!(every > 0, "can't sample one in 0 events");
+        self.find_instance(instance_name).sample_every = Some(every);
+        self
+    }
+
+    /// Record at most `per_second` events per second from the tracepoint `instance_name`
+    /// across all threads, allowing bursts of up to `burst` events (a token bucket).
+    /// Whenever an event is recorded after some were dropped, an `<instance>_suppressed`
+    /// event with the number of dropped events (from the `<class>_suppressed` class) is
+    /// recorded right before it, along with the provider's
+    /// [common fields](::Provider::add_common_fields). Only rate limiting is reported: if the
+    /// instance is also [sampled](::EventClass::sample_instance), sampling runs first and the
+    /// count only covers the sampled events rate limiting dropped, not the hits sampling
+    /// dropped. Generation fails with
+    /// [`Error::SuppressionNameTaken`](::Error::SuppressionNameTaken) if the provider already
+    /// has a class or tracepoint by either name.
+    /// Panics if the class has no such instance, or if either limit is 0.
+    pub fn rate_limit_instance(&mut self, instance_name: &str, per_second: u32, burst: u32) -> &mut Self {
+        assert!(per_second > 0 && burst > 0, "rate limits must allow some events");
+        self.find_instance(instance_name).rate_limit = Some(RateLimit { per_second, burst });
+        self
+    }
+
+    fn find_instance(&mut self, instance_name: &str) -> &mut EventInstance {
+        let class_name = &self.class_name;
+        self.instances.iter_mut()
+            .find(|i| i.name == instance_name)
+            .unwrap_or_else(|| panic!("class {} has no instance {}", class_name, instance_name))
+    }
+
+    /// Adds fields described by `(name, type)` pairs, with types spelled like in
+    /// [schema files](schema/index.html).
+    /// This is what `#[derive(CtfFields)]` from the `lttng-ust-derive` crate generates as
+    /// `CTF_FIELDS`, so that a struct can be traced as a whole:
+    ///
+    /// ```ignore
+    /// provider.create_class(RequestStats::CTF_CLASS)
+    ///     .add_derived_fields(RequestStats::CTF_FIELDS)
+    ///     .instantiate(RequestStats::CTF_INSTANCE);
+    /// ```
+    ///
+    /// The derived `emit` method calls the C function behind the tracepoint directly, passing
+    /// exactly the fields of the struct. So the class can't have any other field, including
+    /// [common fields](::Provider::add_common_fields), nor a
+    /// [caller location](::EventClass::add_caller_location), and its instances can't be
+    /// sampled or rate limited. Generation fails with
+    /// [`Error::DerivedClassMismatch`](::Error::DerivedClassMismatch) otherwise.
+    ///
+    /// Panics if one of the types is invalid.
+    pub fn add_derived_fields(&mut self, fields: &[(&str, &str)]) -> &mut Self {
+        let mut derived = Vec::new();
+        for &(name, spec) in fields {
+            let args: Vec<&str> = spec.split_whitespace().collect();
+            match schema::parse_field(name, &args) {
+                Ok(field) => derived.push(field),
+                Err(e) => panic!("invalid type for field `{}`: {}", name, e),
+            }
+        }
+        self.fields.extend(derived.iter().cloned());
+        self.derived_fields.get_or_insert_with(Vec::new).extend(derived);
+        self
+    }
+
+    /// Whether the fields the C side expects are exactly what a derived `emit` passes it
+    fn matches_derived_fields(&self) -> bool {
+        match self.derived_fields {
+            Some(ref derived) => *derived == self.fields && !self.caller_location &&
+                                 !self.instances.iter().any(|i| i.is_limited()),
+            None => true,
+        }
+    }
+
+    /// Record the source location tracepoints of this class are fired from.
+    /// This adds `caller_file` (a [SequenceText](CTFType::SequenceText)), `caller_line` and
+    /// `caller_column` (both `u32`) after the other fields. The generated Rust functions are
+    /// `#[track_caller]`, so these are filled in automatically and callers don't pass them.
+    /// C callers do pass them, as regular arguments.
+    pub fn add_caller_location(&mut self) -> &mut Self {
+        self.caller_location = true;
+        self
+    }
+
+    /// Every field recorded by the C side, including synthetic ones like the caller location
+    fn c_fields(&self) -> Vec<Field> {
+        let mut fields = Vec::new();
+        for field in &self.fields {
+            if let Some(flag) = field.presence_name() {
+                fields.push(Field::new(flag, CTFType::Integer(CIntegerType::U8)));
+            }
+            match field.ctf_type.lowered() {
+                Some(lowered) => fields.extend(lowered.into_iter().map(|(ty, suffix)| {
+                    let mut c_field = Field::new(format!("{}{}", field.name, suffix), ty);
+                    c_field.description = field.description.clone();
+                    c_field.unit = field.unit.clone();
+                    c_field.sensitive = field.sensitive;
+                    c_field
+                })),
+                None => fields.push(field.clone()),
+            }
+        }
+        if self.caller_location {
+            fields.push(Field::new("caller_file".into(), CTFType::SequenceText));
+            fields.push(Field::new("caller_line".into(), CTFType::Integer(CIntegerType::U32)));
+            fields.push(Field::new("caller_column".into(), CTFType::Integer(CIntegerType::U32)));
+        }
+        fields
+    }
+
     /// Instantiate the class, creating a new tracepoint.
     /// See the [module level documentation](index.html) for examples.
     pub fn instantiate<S: Into<String>>(&mut self, instance_name: S) -> &mut Self {
@@ -163,35 +527,93 @@ impl EventClass {
 }
 
 /// A field in a tracing event
+#[derive(Clone,PartialEq,Debug)]
 pub struct Field {
     ctf_type: CTFType,
     name: String,
+    optional: bool,
+    /// The type `Serde` fields take, as a path usable from the generated bindings
+    rust_type: Option<String>,
+    description: Option<String>,
+    unit: Option<String>,
+    /// Recorded according to the `Generator`'s `RedactionPolicy`
+    sensitive: bool,
 }
 
 impl Field {
     fn new(name: String, ctf_type: CTFType) -> Self {
         Self {
             ctf_type, name,
+            optional: false,
+            rust_type: None,
+            description: None,
+            unit: None,
+            sensitive: false,
         }
     }
+
+    /// Lazily formatted and serde fields can't be optional, which `Generator` reports
+    fn optional(name: String, ctf_type: CTFType) -> Self {
+        let mut field = Self::new(name, ctf_type);
+        field.optional = true;
+        field
+    }
+
+    /// The `u8` flag recorded in front of an optional field, telling whether it is present
+    fn presence_name(&self) -> Option<String> {
+        if self.optional {
+            Some(format!("{}_present", self.name))
+        } else {
+            None
+        }
+    }
+
+    fn serde(name: String, encoding: SerdeEncoding, rust_type: String) -> Self {
+        let mut field = Self::new(name, CTFType::Serde(encoding));
+        field.rust_type = Some(rust_type);
+        field
+    }
 }
 
 /// An instantiated [EventClass](::EventClass).
 /// Every `EventInstance` represents a new tracepoint in the final binary
+#[derive(Clone,PartialEq,Debug)]
 pub struct EventInstance {
     name: String,
     level: LogLevel,
+    description: Option<String>,
+    emf_uri: Option<String>,
+    sample_every: Option<u32>,
+    rate_limit: Option<RateLimit>,
 }
 
 impl EventInstance {
     fn new(name: String, level: LogLevel) -> Self {
         EventInstance {
             name, level,
+            description: None,
+            emf_uri: None,
+            sample_every: None,
+            rate_limit: None,
         }
     }
+
+    /// Whether some hits of this tracepoint may be dropped before reaching C
+    fn is_limited(&self) -> bool {
+        self.sample_every.is_some() || self.rate_limit.is_some()
+    }
 }
 
-/// Represents the log level for a given tracepoint
+/// A token bucket, see [`EventClass::rate_limit_instance`](::EventClass::rate_limit_instance)
+#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+struct RateLimit {
+    per_second: u32,
+    burst: u32,
+}
+
+/// Represents the log level for a given tracepoint.
+/// Levels are ordered from most to least severe, so `LogLevel::Error < LogLevel::Info`.
+#[derive(Copy,Clone,PartialEq,Eq,PartialOrd,Ord,Debug)]
 pub enum LogLevel {
     /// Corresponds to the `TRACE_EMERG` log level
     Emergency,
@@ -254,6 +676,10 @@ impl LogLevel {
 pub enum CIntegerType {
     I8, I16, I32, I64,
     U8, U16, U32, U64,
+    /// Pointer-sized, `isize` in Rust and `intptr_t` in C
+    Isize,
+    /// Pointer-sized, `usize` in Rust and `size_t` in C
+    Usize,
 }
 
 impl CIntegerType {
@@ -267,7 +693,9 @@ impl CIntegerType {
             CIntegerType::I32 =>  "int32_t",
             CIntegerType::U32 => "uint32_t",
             CIntegerType::I64 =>  "int64_t",
-            CIntegerType::U64 => "uint64_t"
+            CIntegerType::U64 => "uint64_t",
+            CIntegerType::Isize => "intptr_t",
+            CIntegerType::Usize => "size_t",
         }
     }
 
@@ -281,7 +709,9 @@ impl CIntegerType {
             CIntegerType::I32 =>  "const int32_t *",
             CIntegerType::U32 => "const uint32_t *",
             CIntegerType::I64 =>  "const int64_t *",
-            CIntegerType::U64 => "const uint64_t *"
+            CIntegerType::U64 => "const uint64_t *",
+            CIntegerType::Isize => "const intptr_t *",
+            CIntegerType::Usize => "const size_t *",
         }
     }
 
@@ -296,8 +726,15 @@ impl CIntegerType {
             CIntegerType::U32 => "u32",
             CIntegerType::I64 => "i64",
             CIntegerType::U64 => "u64",
+            CIntegerType::Isize => "isize",
+            CIntegerType::Usize => "usize",
         }
     }
+
+    /// Whether bindgen may pick a different Rust type for the C type, so we need to cast
+    fn is_pointer_sized(&self) -> bool {
+        matches!(*self, CIntegerType::Isize | CIntegerType::Usize)
+    }
 }
 
 /// Represents a C float type
@@ -325,6 +762,35 @@ impl CFloatType {
     }
 }
 
+/// How [Serde](CTFType::Serde) fields are encoded.
+/// The crate doing the encoding has to be a dependency of the crate the bindings are
+/// included in, along with `serde` itself.
+#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+pub enum SerdeEncoding {
+    /// CBOR (RFC 8949), written with the `ciborium` crate.
+    Cbor,
+    /// The default format of `bincode` 1.x, written with the `bincode` crate.
+    Bincode,
+}
+
+impl SerdeEncoding {
+    /// How the encoding is spelled in schema files, and the name of its Rust encoding function
+    fn name(&self) -> &'static str {
+        match *self {
+            SerdeEncoding::Cbor => "cbor",
+            SerdeEncoding::Bincode => "bincode",
+        }
+    }
+
+    /// Appended to the name of the recorded field, so readers know how to decode it
+    fn field_suffix(&self) -> &'static str {
+        match *self {
+            SerdeEncoding::Cbor => "_cbor",
+            SerdeEncoding::Bincode => "_bincode",
+        }
+    }
+}
+
 /// Represents a CTF type
 #[derive(Copy,Clone,PartialEq,Eq,Debug)]
 
@@ -372,8 +838,12 @@ pub enum CTFType {
     /// persisted.
     /// Maps to `ctf_array_nowrite`.
     ArrayNoWrite(CIntegerType, i32),
+    /// A statically sized array of characters which is available to event filters, but is not
+    /// persisted.
+    /// Maps to `ctf_array_text_nowrite`.
+    ArrayTextNoWrite(i32),
     /* Things to add later: */
-    // ArrayNetwork{NoWrite,Hex,NoWriteHex}, ArrayTextNoWrite
+    // ArrayNetwork{NoWrite,Hex,NoWriteHex}
     /// Dynamically sized array of integers
     /// Maps to `ctf_sequence`.
     Sequence(CIntegerType),
@@ -389,6 +859,63 @@ pub enum CTFType {
     /// Dynamically-sized array, displayed as text, but is not persisted.
     /// Maps to `ctf_sequence_text_nowrite`.
     SequenceTextNoWrite,
+    /// Text produced by a Rust value's `Display` implementation.
+    /// The generated Rust function takes an `impl Display`, which is only formatted (into a
+    /// reused thread-local buffer) when the tracepoint is enabled.
+    /// Maps to `ctf_sequence_text`.
+    Display,
+    /// Like [Display](CTFType::Display), but formatted with `Debug`.
+    /// Maps to `ctf_sequence_text`.
+    Debug,
+    /// A Rust `bool`, recorded as a `uint8_t` that is either 0 or 1.
+    /// Maps to `ctf_integer`.
+    Bool,
+    /// A Rust `char`, recorded as its `uint32_t` code point.
+    /// Maps to `ctf_integer`.
+    Char,
+    /// A raw pointer, taken as a `*const c_void` and recorded as a `size_t` printed in hex.
+    /// Maps to `ctf_integer_hex`.
+    Pointer,
+    /// A Rust `u128`, recorded as two `uint64_t` fields, `<name>_hi` and `<name>_lo`.
+    /// Maps to `ctf_integer`.
+    U128,
+    /// A Rust `i128`, recorded as an `int64_t` `<name>_hi` and a `uint64_t` `<name>_lo`.
+    /// Maps to `ctf_integer`.
+    I128,
+    /// A `Duration`, recorded as a `uint64_t` number of nanoseconds (saturating at `u64::MAX`).
+    /// Maps to `ctf_integer`.
+    Duration,
+    /// An `Ipv4Addr`, recorded as its 4 bytes in network order.
+    /// Maps to `ctf_array`.
+    Ipv4Addr,
+    /// An `Ipv6Addr`, recorded as its 16 bytes in network order.
+    /// Maps to `ctf_array`.
+    Ipv6Addr,
+    /// An `IpAddr`, recorded as the 16 bytes of an `Ipv6Addr` in network order. IPv4
+    /// addresses are recorded as IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`).
+    /// Maps to `ctf_array`.
+    IpAddr,
+    /// A `SocketAddr`, recorded like an [IpAddr](CTFType::IpAddr) followed by a `uint16_t`
+    /// `<name>_port`.
+    /// Maps to `ctf_array` and `ctf_integer`.
+    SocketAddr,
+    /// A `&Path`, recorded byte for byte as text. Not available with `Generator::no_std`.
+    /// Maps to `ctf_sequence_text`.
+    Path,
+    /// A `&OsStr`, recorded byte for byte as text. Not available with `Generator::no_std`.
+    /// Maps to `ctf_sequence_text`.
+    OsStr,
+    /// Any `serde::Serialize` value, recorded as the bytes of its encoding.
+    /// Like [Display](CTFType::Display) fields, the value is only encoded (into a reused
+    /// thread-local buffer) when the tracepoint is enabled. Values that fail to encode are
+    /// recorded as an empty sequence. These fields need to know the Rust type they take, so
+    /// they are added with [`EventClass::add_serde_field`](::EventClass::add_serde_field);
+    /// generating a serde field added any other way fails with
+    /// [`Error::MissingSerdeType`](::Error::MissingSerdeType).
+    /// The recorded field name ends with the encoding, e.g. `<name>_cbor`.
+    /// Not available with `Generator::no_std`.
+    /// Maps to `ctf_sequence`.
+    Serde(SerdeEncoding),
     /// Enumeration value.
     /// TODO: some sort of proc-macro skulduggery is probably required here.
     /// Maps to `ctf_enum`.
@@ -401,12 +928,38 @@ pub enum CTFType {
 
 impl CTFType {
     fn is_sequence(&self) -> bool {
+        matches!(*self, CTFType::Sequence(_) | CTFType::SequenceNoWrite(_) | CTFType::SequenceText |
+                        CTFType::SequenceTextNoWrite | CTFType::Display | CTFType::Debug)
+    }
+
+    /// The plain CTF types the C side records this Rust-only type as, along with the suffix
+    /// appended to the field name for each of them. `None` for types C understands directly.
+    /// `lttng_ust::decode` knows about these suffixes, so keep it in sync.
+    fn lowered(&self) -> Option<Vec<(CTFType, &'static str)>> {
+        use CIntegerType::*;
+        let lowered = match *self {
+            CTFType::Bool => vec![(CTFType::Integer(U8), "")],
+            CTFType::Char => vec![(CTFType::Integer(U32), "")],
+            CTFType::Pointer => vec![(CTFType::IntegerHex(Usize), "")],
+            CTFType::U128 => vec![(CTFType::Integer(U64), "_hi"), (CTFType::Integer(U64), "_lo")],
+            CTFType::I128 => vec![(CTFType::Integer(I64), "_hi"), (CTFType::Integer(U64), "_lo")],
+            CTFType::Duration => vec![(CTFType::Integer(U64), "")],
+            CTFType::Ipv4Addr => vec![(CTFType::Array(U8, 4), "")],
+            CTFType::Ipv6Addr | CTFType::IpAddr => vec![(CTFType::Array(U8, 16), "")],
+            CTFType::SocketAddr => vec![(CTFType::Array(U8, 16), ""), (CTFType::Integer(U16), "_port")],
+            CTFType::Path | CTFType::OsStr => vec![(CTFType::SequenceText, "")],
+            CTFType::Serde(encoding) => vec![(CTFType::Sequence(U8), encoding.field_suffix())],
+            _ => return None,
+        };
+        Some(lowered)
+    }
+
+    /// The format spec and trait used for fields formatted only when the tracepoint is enabled
+    fn lazy_text_format(&self) -> Option<(&'static str, &'static str)> {
         match *self {
-            CTFType::Sequence(_) |
-            CTFType::SequenceNoWrite(_) |
-            CTFType::SequenceText |
-            CTFType::SequenceTextNoWrite => true,
-            _ => false,
+            CTFType::Display => Some(("{}", "fmt::Display")),
+            CTFType::Debug => Some(("{:?}", "fmt::Debug")),
+            _ => None,
         }
     }
 }
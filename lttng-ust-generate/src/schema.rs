@@ -0,0 +1,740 @@
+//! A plain-text format for describing providers.
+//!
+//! Schema files let you describe tracepoints without writing any Rust, which is what the
+//! `lttng-ust-gen` binary consumes. A schema mirrors the builder API: every `class` belongs
+//! to the `provider` above it, and every `field` and `instance` to the `class` above it.
+//!
+//! ```text
+//! # Comments run to the end of the line
+//! provider my_first_rust_provider
+//!     class my_first_class
+//!         field my_integer_field integer i32
+//!         field my_string_field sequence_text
+//!         instance my_first_tracepoint
+//!         instance my_loud_tracepoint warning
+//!     class request_done
+//!         description "A request was answered"
+//!         field latency integer u64
+//!             unit ns
+//!         instance request_done info
+//!             emf_uri "https://example.com/model.emf#request_done"
+//! ```
+//!
+//! Field types are spelled after the [`CTFType`](::CTFType) variant they map to, in
+//! `snake_case`, followed by any parameters (e.g. `array u8 16`, `float double`).
+//! Fields added with [`EventClass::add_optional_field`](::EventClass::add_optional_field)
+//! have `optional` in front of their type, and may be followed by a `presence` statement
+//! naming the `u8` flag recorded in front of them. [`write`](::schema::write) always writes
+//! one, so programs reading traces don't have to guess which recorded fields are flags.
+//! Fields added with [`EventClass::add_serde_field`](::EventClass::add_serde_field) are
+//! spelled `serde <encoding> <rust type>`, e.g. `serde cbor crate::config::Config`.
+//! A `common` statement in a provider takes the same arguments as `field`, and corresponds
+//! to [`Provider::add_common_fields`](::Provider::add_common_fields).
+//! A `location` statement in a class corresponds to
+//! [`EventClass::add_caller_location`](::EventClass::add_caller_location).
+//! Instance levels are spelled after their [`LogLevel`](::LogLevel), and default to
+//! `debug_line` like [`EventClass::instantiate`](::EventClass::instantiate) does.
+//! `description`, `unit`, `emf_uri` and `sensitive` statements annotate whatever was declared
+//! right before them, see [`EventClass::describe`](::EventClass::describe) and the methods
+//! after it.
+//! `sample <every>` and `rate_limit <per second> <burst>` statements after an instance
+//! correspond to [`EventClass::sample_instance`](::EventClass::sample_instance) and
+//! [`EventClass::rate_limit_instance`](::EventClass::rate_limit_instance).
+//! Arguments containing whitespace may be double-quoted, with `\n` standing for a line break.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use ::{CFloatType, CIntegerType, CTFType, Field, FieldGroup, LogLevel, Provider, RateLimit, SerdeEncoding};
+
+/// An error encountered while reading a schema
+#[derive(Debug)]
+pub enum ParseError {
+    /// The schema file couldn't be read
+    Io(io::Error),
+    /// The schema is malformed
+    Syntax {
+        /// The (1-based) line the error occurred on
+        line: usize,
+        /// What went wrong
+        message: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Io(ref e) => write!(f, "failed to read schema: {}", e),
+            ParseError::Syntax { line, ref message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            ParseError::Io(ref e) => Some(e),
+            ParseError::Syntax { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+/// Parse every provider described by `text`.
+pub fn parse(text: &str) -> Result<Vec<Provider>, ParseError> {
+    let mut providers: Vec<Provider> = Vec::new();
+    let mut last = Annotated::Nothing;
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let syntax = |message: String| ParseError::Syntax { line: line_no, message };
+        let tokens = tokenize(line).map_err(&syntax)?;
+        let mut tokens = tokens.iter().map(|t| &t[..]);
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "provider" => {
+                let name = single_arg(keyword, &args).map_err(&syntax)?;
+                providers.push(Provider::new(name));
+                last = Annotated::Nothing;
+            }
+            "common" => {
+                if args.len() < 2 {
+                    return Err(syntax("expected `common <name> [optional] <type>`".into()));
+                }
+                let field = parse_field(args[0], &args[1..]).map_err(&syntax)?;
+                let provider = providers.last_mut()
+                    .ok_or_else(|| syntax("`common` outside of a provider".into()))?;
+                provider.add_common_fields(&FieldGroup { fields: vec![field] });
+                last = Annotated::CommonField;
+            }
+            "class" => {
+                let name = single_arg(keyword, &args).map_err(&syntax)?;
+                let provider = providers.last_mut()
+                    .ok_or_else(|| syntax("`class` outside of a provider".into()))?;
+                provider.create_class(name);
+                last = Annotated::Class;
+            }
+            "field" => {
+                if args.len() < 2 {
+                    return Err(syntax("expected `field <name> [optional] <type>`".into()));
+                }
+                let field = parse_field(args[0], &args[1..]).map_err(&syntax)?;
+                let class = providers.last_mut().and_then(|p| p.classes.last_mut())
+                    .ok_or_else(|| syntax("`field` outside of a class".into()))?;
+                class.fields.push(field);
+                last = Annotated::Field;
+            }
+            "location" => {
+                if !args.is_empty() {
+                    return Err(syntax("`location` takes no arguments".into()));
+                }
+                let class = providers.last_mut().and_then(|p| p.classes.last_mut())
+                    .ok_or_else(|| syntax("`location` outside of a class".into()))?;
+                class.add_caller_location();
+            }
+            "instance" => {
+                let level = match args.len() {
+                    1 => LogLevel::DebugLine,
+                    2 => parse_log_level(args[1]).map_err(&syntax)?,
+                    _ => return Err(syntax("expected `instance <name> [level]`".into())),
+                };
+                let class = providers.last_mut().and_then(|p| p.classes.last_mut())
+                    .ok_or_else(|| syntax("`instance` outside of a class".into()))?;
+                class.instantiate_with_level(args[0], level);
+                last = Annotated::Instance;
+            }
+            "sensitive" => {
+                if !args.is_empty() {
+                    return Err(syntax("`sensitive` takes no arguments".into()));
+                }
+                match (last, providers.last_mut()) {
+                    (Annotated::Field, Some(provider)) => {
+                        let class = provider.classes.last_mut().unwrap();
+                        class.fields.last_mut().unwrap().sensitive = true;
+                    }
+                    (Annotated::CommonField, Some(provider)) => {
+                        let i = provider.common_fields.len() - 1;
+                        provider.common_fields[i].sensitive = true;
+                        for class in &mut provider.classes {
+                            class.fields[i].sensitive = true;
+                        }
+                    }
+                    _ => return Err(syntax("nothing for `sensitive` to apply to".into())),
+                }
+            }
+            "sample" | "rate_limit" => {
+                let limits = args.iter()
+                    .map(|a| a.parse::<u32>().ok().filter(|&n| n > 0))
+                    .collect::<Option<Vec<u32>>>();
+                let limits = match (keyword, limits) {
+                    ("sample", Some(ref limits)) if limits.len() == 1 => limits.clone(),
+                    ("rate_limit", Some(ref limits)) if limits.len() == 2 => limits.clone(),
+                    ("sample", _) => return Err(syntax("expected `sample <every>`".into())),
+                    _ => return Err(syntax("expected `rate_limit <per second> <burst>`".into())),
+                };
+                let instance = match (last, providers.last_mut()) {
+                    (Annotated::Instance, Some(provider)) =>
+                        provider.classes.last_mut().unwrap().instances.last_mut().unwrap(),
+                    _ => return Err(syntax(format!("nothing for `{}` to apply to", keyword))),
+                };
+                if keyword == "sample" {
+                    instance.sample_every = Some(limits[0]);
+                } else {
+                    instance.rate_limit = Some(RateLimit { per_second: limits[0], burst: limits[1] });
+                }
+            }
+            "presence" => {
+                let flag = single_arg(keyword, &args).map_err(&syntax)?;
+                let field = match (last, providers.last()) {
+                    (Annotated::Field, Some(provider)) => provider.classes.last().unwrap().fields.last().unwrap(),
+                    (Annotated::CommonField, Some(provider)) => provider.common_fields.last().unwrap(),
+                    _ => return Err(syntax("nothing for `presence` to apply to".into())),
+                };
+                match field.presence_name() {
+                    Some(ref recorded) if recorded == flag => {}
+                    Some(recorded) =>
+                        return Err(syntax(format!("the presence flag of `{}` is `{}`", field.name, recorded))),
+                    None => return Err(syntax(format!("`{}` isn't optional", field.name))),
+                }
+            }
+            "description" | "unit" | "emf_uri" => {
+                let value = single_arg(keyword, &args).map_err(&syntax)?.to_string();
+                let provider = providers.last_mut();
+                match (keyword, last, provider) {
+                    ("description", Annotated::Class, Some(provider)) => {
+                        provider.classes.last_mut().unwrap().description = Some(value);
+                    }
+                    ("description", Annotated::Field, Some(provider)) |
+                    ("unit", Annotated::Field, Some(provider)) => {
+                        let class = provider.classes.last_mut().unwrap();
+                        annotate(class.fields.last_mut().unwrap(), keyword, value);
+                    }
+                    ("description", Annotated::CommonField, Some(provider)) |
+                    ("unit", Annotated::CommonField, Some(provider)) => {
+                        // Common fields have already been copied into every class
+                        let i = provider.common_fields.len() - 1;
+                        annotate(&mut provider.common_fields[i], keyword, value.clone());
+                        for class in &mut provider.classes {
+                            annotate(&mut class.fields[i], keyword, value.clone());
+                        }
+                    }
+                    ("description", Annotated::Instance, Some(provider)) |
+                    ("emf_uri", Annotated::Instance, Some(provider)) => {
+                        let class = provider.classes.last_mut().unwrap();
+                        let instance = class.instances.last_mut().unwrap();
+                        if keyword == "description" {
+                            instance.description = Some(value);
+                        } else {
+                            instance.emf_uri = Some(value);
+                        }
+                    }
+                    _ => return Err(syntax(format!("nothing for `{}` to apply to", keyword))),
+                }
+            }
+            other => return Err(syntax(format!("unknown statement `{}`", other))),
+        }
+    }
+    Ok(providers)
+}
+
+/// What `description`, `unit`, `emf_uri`, `sensitive` and `presence` statements apply to: the
+/// last thing declared
+#[derive(Copy,Clone)]
+enum Annotated {
+    Nothing,
+    CommonField,
+    Class,
+    Field,
+    Instance,
+}
+
+/// Apply a `description` or `unit` statement to `field`
+fn annotate(field: &mut Field, keyword: &str, value: String) {
+    if keyword == "unit" {
+        field.unit = Some(value);
+    } else {
+        field.description = Some(value);
+    }
+}
+
+/// Parse every provider described by the schema file at `path`.
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Vec<Provider>, ParseError> {
+    let text = fs::read_to_string(path)?;
+    parse(&text)
+}
+
+/// Write `providers` out in schema form, such that [`parse`](::schema::parse) gives them back.
+pub fn write<W: Write>(outf: &mut W, providers: &[Provider]) -> io::Result<()> {
+    for provider in providers {
+        writeln!(outf, "provider {}", quote(&provider.name))?;
+        for field in &provider.common_fields {
+            writeln!(outf, "    common {} {}", quote(&field.name), field_type_name(field)?)?;
+            write_field_annotations(outf, field, "        ")?;
+        }
+        for class in &provider.classes {
+            writeln!(outf, "    class {}", quote(&class.class_name))?;
+            if let Some(ref description) = class.description {
+                writeln!(outf, "        description {}", quote(description))?;
+            }
+            // Classes start with the common fields, which parsing adds back
+            for field in &class.fields[provider.common_fields.len()..] {
+                writeln!(outf, "        field {} {}", quote(&field.name), field_type_name(field)?)?;
+                write_field_annotations(outf, field, "            ")?;
+            }
+            if class.caller_location {
+                writeln!(outf, "        location")?;
+            }
+            for instance in &class.instances {
+                writeln!(outf, "        instance {} {}",
+                         quote(&instance.name), log_level_name(&instance.level))?;
+                if let Some(ref description) = instance.description {
+                    writeln!(outf, "            description {}", quote(description))?;
+                }
+                if let Some(ref uri) = instance.emf_uri {
+                    writeln!(outf, "            emf_uri {}", quote(uri))?;
+                }
+                if let Some(every) = instance.sample_every {
+                    writeln!(outf, "            sample {}", every)?;
+                }
+                if let Some(limit) = instance.rate_limit {
+                    writeln!(outf, "            rate_limit {} {}", limit.per_second, limit.burst)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Split a line into whitespace separated tokens, honoring double quotes and comments
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            break;
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => token.push('\n'),
+                        Some(escaped) => token.push(escaped),
+                        None => return Err("unterminated escape sequence".into()),
+                    },
+                    Some(c) => token.push(c),
+                    None => return Err("unterminated string".into()),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '#' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+fn quote(s: &str) -> String {
+    if !s.is_empty() && !s.chars().any(|c| c.is_whitespace() || c == '"' || c == '#' || c == '\\') {
+        return s.into();
+    }
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn single_arg<'a>(keyword: &str, args: &[&'a str]) -> Result<&'a str, String> {
+    if args.len() == 1 {
+        Ok(args[0])
+    } else {
+        Err(format!("expected `{} <name>`", keyword))
+    }
+}
+
+/// Parse `[optional] <type>`, as found after the name of a field
+pub(crate) fn parse_field(name: &str, args: &[&str]) -> Result<Field, String> {
+    let optional = args.first() == Some(&"optional");
+    let type_args = if optional { &args[1..] } else { args };
+    if type_args.is_empty() {
+        return Err("missing field type".into());
+    }
+    let mut field = if type_args[0] == "serde" {
+        if type_args.len() != 3 {
+            return Err("expected `serde <encoding> <rust type>`".into());
+        }
+        Field::serde(name.into(), parse_serde_encoding(type_args[1])?, type_args[2].into())
+    } else {
+        Field::new(name.into(), parse_ctf_type(type_args)?)
+    };
+    if optional {
+        if field.ctf_type.lazy_text_format().is_some() || field.rust_type.is_some() {
+            return Err(format!("`{}` fields can't be optional", type_args[0]));
+        }
+        field.optional = true;
+    }
+    Ok(field)
+}
+
+fn parse_ctf_type(args: &[&str]) -> Result<CTFType, String> {
+    let int = |i: usize| args.get(i).ok_or_else(|| "missing integer type".to_string())
+        .and_then(|s| parse_integer_type(s));
+    let float = |i: usize| args.get(i).ok_or_else(|| "missing float type".to_string())
+        .and_then(|s| parse_float_type(s));
+    let len = |i: usize| args.get(i).ok_or_else(|| "missing array length".to_string())
+        .and_then(|s| s.parse::<i32>().map_err(|e| format!("bad array length `{}`: {}", s, e)));
+
+    let (ty, arity) = match args[0] {
+        "integer" => (CTFType::Integer(int(1)?), 2),
+        "integer_nowrite" => (CTFType::IntegerNoWrite(int(1)?), 2),
+        "integer_hex" => (CTFType::IntegerHex(int(1)?), 2),
+        "integer_network" => (CTFType::IntegerNetwork(int(1)?), 2),
+        "integer_network_hex" => (CTFType::IntegerNetworkHex(int(1)?), 2),
+        "float" => (CTFType::Float(float(1)?), 2),
+        "float_nowrite" => (CTFType::FloatNoWrite(float(1)?), 2),
+        "string" => (CTFType::String, 1),
+        "string_nowrite" => (CTFType::StringNoWrite, 1),
+        "array" => (CTFType::Array(int(1)?, len(2)?), 3),
+        "array_text" => (CTFType::ArrayText(len(1)?), 2),
+        "array_text_nowrite" => (CTFType::ArrayTextNoWrite(len(1)?), 2),
+        "array_nowrite" => (CTFType::ArrayNoWrite(int(1)?, len(2)?), 3),
+        "sequence" => (CTFType::Sequence(int(1)?), 2),
+        "sequence_nowrite" => (CTFType::SequenceNoWrite(int(1)?), 2),
+        "sequence_text" => (CTFType::SequenceText, 1),
+        "sequence_text_nowrite" => (CTFType::SequenceTextNoWrite, 1),
+        "display" => (CTFType::Display, 1),
+        "debug" => (CTFType::Debug, 1),
+        "bool" => (CTFType::Bool, 1),
+        "char" => (CTFType::Char, 1),
+        "pointer" => (CTFType::Pointer, 1),
+        "u128" => (CTFType::U128, 1),
+        "i128" => (CTFType::I128, 1),
+        "duration" => (CTFType::Duration, 1),
+        "ipv4_addr" => (CTFType::Ipv4Addr, 1),
+        "ipv6_addr" => (CTFType::Ipv6Addr, 1),
+        "ip_addr" => (CTFType::IpAddr, 1),
+        "socket_addr" => (CTFType::SocketAddr, 1),
+        "path" => (CTFType::Path, 1),
+        "os_str" => (CTFType::OsStr, 1),
+        other => return Err(format!("unknown field type `{}`", other)),
+    };
+    if args.len() != arity {
+        return Err(format!("`{}` takes {} argument(s)", args[0], arity - 1));
+    }
+    Ok(ty)
+}
+
+fn write_field_annotations<W: Write>(outf: &mut W, field: &Field, indent: &str) -> io::Result<()> {
+    if let Some(ref description) = field.description {
+        writeln!(outf, "{}description {}", indent, quote(description))?;
+    }
+    if let Some(ref unit) = field.unit {
+        writeln!(outf, "{}unit {}", indent, quote(unit))?;
+    }
+    if field.sensitive {
+        writeln!(outf, "{}sensitive", indent)?;
+    }
+    if let Some(flag) = field.presence_name() {
+        writeln!(outf, "{}presence {}", indent, quote(&flag))?;
+    }
+    Ok(())
+}
+
+/// How `field` is spelled after its name, e.g. `optional integer u32`
+pub(crate) fn field_type_name(field: &Field) -> io::Result<String> {
+    let ty = match field.rust_type {
+        Some(ref rust_type) => format!("{} {}", ctf_type_name(&field.ctf_type)?, quote(rust_type)),
+        None => ctf_type_name(&field.ctf_type)?,
+    };
+    if field.optional {
+        Ok(format!("optional {}", ty))
+    } else {
+        Ok(ty)
+    }
+}
+
+fn ctf_type_name(ty: &CTFType) -> io::Result<String> {
+    Ok(match *ty {
+        CTFType::Integer(i) => format!("integer {}", i.rust_type()),
+        CTFType::IntegerNoWrite(i) => format!("integer_nowrite {}", i.rust_type()),
+        CTFType::IntegerHex(i) => format!("integer_hex {}", i.rust_type()),
+        CTFType::IntegerNetwork(i) => format!("integer_network {}", i.rust_type()),
+        CTFType::IntegerNetworkHex(i) => format!("integer_network_hex {}", i.rust_type()),
+        CTFType::Float(f) => format!("float {}", float_type_name(f)),
+        CTFType::FloatNoWrite(f) => format!("float_nowrite {}", float_type_name(f)),
+        CTFType::String => "string".into(),
+        CTFType::StringNoWrite => "string_nowrite".into(),
+        CTFType::Array(i, l) => format!("array {} {}", i.rust_type(), l),
+        CTFType::ArrayText(l) => format!("array_text {}", l),
+        CTFType::ArrayTextNoWrite(l) => format!("array_text_nowrite {}", l),
+        CTFType::ArrayNoWrite(i, l) => format!("array_nowrite {} {}", i.rust_type(), l),
+        CTFType::Sequence(i) => format!("sequence {}", i.rust_type()),
+        CTFType::SequenceNoWrite(i) => format!("sequence_nowrite {}", i.rust_type()),
+        CTFType::SequenceText => "sequence_text".into(),
+        CTFType::SequenceTextNoWrite => "sequence_text_nowrite".into(),
+        CTFType::Display => "display".into(),
+        CTFType::Debug => "debug".into(),
+        CTFType::Bool => "bool".into(),
+        CTFType::Char => "char".into(),
+        CTFType::Pointer => "pointer".into(),
+        CTFType::U128 => "u128".into(),
+        CTFType::I128 => "i128".into(),
+        CTFType::Duration => "duration".into(),
+        CTFType::Ipv4Addr => "ipv4_addr".into(),
+        CTFType::Ipv6Addr => "ipv6_addr".into(),
+        CTFType::IpAddr => "ip_addr".into(),
+        CTFType::SocketAddr => "socket_addr".into(),
+        CTFType::Path => "path".into(),
+        CTFType::OsStr => "os_str".into(),
+        CTFType::Serde(e) => format!("serde {}", e.name()),
+        CTFType::Enum | CTFType::EnumNoWrite =>
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "enum fields have no schema representation")),
+    })
+}
+
+fn parse_integer_type(s: &str) -> Result<CIntegerType, String> {
+    match s {
+        "i8" => Ok(CIntegerType::I8),
+        "i16" => Ok(CIntegerType::I16),
+        "i32" => Ok(CIntegerType::I32),
+        "i64" => Ok(CIntegerType::I64),
+        "u8" => Ok(CIntegerType::U8),
+        "u16" => Ok(CIntegerType::U16),
+        "u32" => Ok(CIntegerType::U32),
+        "u64" => Ok(CIntegerType::U64),
+        "isize" => Ok(CIntegerType::Isize),
+        "usize" => Ok(CIntegerType::Usize),
+        other => Err(format!("unknown integer type `{}`", other)),
+    }
+}
+
+fn parse_serde_encoding(s: &str) -> Result<SerdeEncoding, String> {
+    match s {
+        "cbor" => Ok(SerdeEncoding::Cbor),
+        "bincode" => Ok(SerdeEncoding::Bincode),
+        other => Err(format!("unknown serde encoding `{}`", other)),
+    }
+}
+
+fn parse_float_type(s: &str) -> Result<CFloatType, String> {
+    match s {
+        "single" => Ok(CFloatType::Single),
+        "double" => Ok(CFloatType::Double),
+        other => Err(format!("unknown float type `{}`", other)),
+    }
+}
+
+fn float_type_name(f: CFloatType) -> &'static str {
+    match f {
+        CFloatType::Single => "single",
+        CFloatType::Double => "double",
+    }
+}
+
+fn parse_log_level(s: &str) -> Result<LogLevel, String> {
+    use LogLevel::*;
+    match s {
+        "emergency" => Ok(Emergency),
+        "alert" => Ok(Alert),
+        "critical" => Ok(Critical),
+        "error" => Ok(Error),
+        "warning" => Ok(Warning),
+        "notice" => Ok(Notice),
+        "info" => Ok(Info),
+        "debug_system" => Ok(DebugSystem),
+        "debug_program" => Ok(DebugProgram),
+        "debug_process" => Ok(DebugProcess),
+        "debug_module" => Ok(DebugModule),
+        "debug_unit" => Ok(DebugUnit),
+        "debug_function" => Ok(DebugFunction),
+        "debug_line" => Ok(DebugLine),
+        "debug" => Ok(Debug),
+        other => Err(format!("unknown log level `{}`", other)),
+    }
+}
+
+fn log_level_name(level: &LogLevel) -> &'static str {
+    use LogLevel::*;
+    match *level {
+        Emergency => "emergency",
+        Alert => "alert",
+        Critical => "critical",
+        Error => "error",
+        Warning => "warning",
+        Notice => "notice",
+        Info => "info",
+        DebugSystem => "debug_system",
+        DebugProgram => "debug_program",
+        DebugProcess => "debug_process",
+        DebugModule => "debug_module",
+        DebugUnit => "debug_unit",
+        DebugFunction => "debug_function",
+        DebugLine => "debug_line",
+        Debug => "debug",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVERYTHING: &str = r#"
+provider http # trailing comment
+    common request_id integer u64
+        description "Id of the request being served"
+        sensitive
+    common worker optional integer_hex u16
+        unit index
+    class request_start
+        description "A request came in\nand is about to be routed"
+        field method string
+        field client ipv6_addr
+            sensitive
+        field body serde cbor "my_crate::Body"
+        field retries optional integer u8
+            presence retries_present
+        field started float double
+            unit s
+        location
+        instance request_start info
+            description "Start of a request"
+            emf_uri "https://example.com/model.emf#start"
+            sample 10
+        instance request_flood warning
+            rate_limit 100 20
+    class nothing
+        instance nothing
+"#;
+
+    fn round_trip(providers: &[Provider]) -> Vec<Provider> {
+        let mut text = Vec::new();
+        write(&mut text, providers).unwrap();
+        parse(&String::from_utf8(text).unwrap()).unwrap()
+    }
+
+    fn syntax_error_line(text: &str) -> usize {
+        match parse(text) {
+            Err(ParseError::Syntax { line, .. }) => line,
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trips_every_statement() {
+        let providers = parse(EVERYTHING).unwrap();
+        assert_eq!(round_trip(&providers), providers);
+
+        let class = &providers[0].classes[0];
+        assert_eq!(class.fields.len(), 7);
+        assert!(class.fields[0].sensitive);
+        assert!(class.fields[1].optional);
+        assert_eq!(class.fields[1].unit.as_ref().map(|s| &s[..]), Some("index"));
+        assert_eq!(class.fields[4].rust_type.as_ref().map(|s| &s[..]), Some("my_crate::Body"));
+        assert!(class.caller_location);
+        assert_eq!(class.description.as_ref().map(|s| &s[..]),
+                   Some("A request came in\nand is about to be routed"));
+        assert_eq!(class.instances[0].sample_every, Some(10));
+        assert_eq!(class.instances[1].rate_limit, Some(RateLimit { per_second: 100, burst: 20 }));
+        assert_eq!(providers[0].classes[1].instances[0].level, LogLevel::DebugLine);
+    }
+
+    #[test]
+    fn writes_what_the_builder_api_declares() {
+        let mut provider = Provider::new("builder");
+        provider.add_common_fields(FieldGroup::new().add_field("tenant", CTFType::SequenceText));
+        provider.create_class("class")
+            .add_field("every_int", CTFType::IntegerNetworkHex(CIntegerType::Isize))
+            .add_field("array", CTFType::Array(CIntegerType::I16, 4))
+            .add_field("text", CTFType::ArrayTextNoWrite(8))
+            .add_field("float", CTFType::FloatNoWrite(CFloatType::Single))
+            .add_field("dbg", CTFType::Debug)
+            .add_field("big", CTFType::I128)
+            .add_field("path", CTFType::Path)
+            .add_field("ip", CTFType::IpAddr)
+            .add_optional_field("peer", CTFType::SocketAddr)
+            .add_optional_field("maybe", CTFType::Char)
+            .add_serde_field("config", SerdeEncoding::Bincode, "Config")
+            .instantiate_with_level("every_level", LogLevel::Emergency);
+        let providers = vec![provider];
+        assert_eq!(round_trip(&providers), providers);
+    }
+
+    #[test]
+    fn writes_the_same_text_twice() {
+        let providers = parse(EVERYTHING).unwrap();
+        let mut first = Vec::new();
+        write(&mut first, &providers).unwrap();
+        let mut second = Vec::new();
+        write(&mut second, &round_trip(&providers)).unwrap();
+        assert_eq!(String::from_utf8(first).unwrap(), String::from_utf8(second).unwrap());
+    }
+
+    #[test]
+    fn quotes_whatever_the_tokenizer_would_split() {
+        for s in &["", "two words", "tab\there", "line\nbreak", "a \"quote\"", "back\\slash",
+                   "not # a comment", "#", "plain"] {
+            assert_eq!(tokenize(&quote(s)).unwrap(), vec![s.to_string()], "quoting {:?}", s);
+        }
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(tokenize(r#"a "b c" d # e"#).unwrap(), vec!["a", "b c", "d"]);
+        assert_eq!(tokenize(r#""\q""#).unwrap(), vec!["q"]);
+        assert!(tokenize("\"unterminated").is_err());
+        assert!(tokenize("\"escape\\").is_err());
+    }
+
+    #[test]
+    fn reports_the_line_of_syntax_errors() {
+        assert_eq!(syntax_error_line("class orphan"), 1);
+        assert_eq!(syntax_error_line("provider p\n\n  class c\n  field f integer u65"), 4);
+        assert_eq!(syntax_error_line("provider p\n  class c\n    field f array u8\n"), 3);
+        assert_eq!(syntax_error_line("provider p\n  sensitive"), 2);
+        assert_eq!(syntax_error_line("provider p\n  class c\n    instance i\n      sample 0"), 4);
+        assert_eq!(syntax_error_line("provider p\n  class c\n    unit ns"), 3);
+        assert_eq!(syntax_error_line("# fine\nprovider \"p"), 2);
+        assert_eq!(syntax_error_line("provider p\n  class c\n    field f optional display"), 3);
+        assert_eq!(syntax_error_line("provider p\n  bogus"), 2);
+        assert_eq!(syntax_error_line("provider p\n  class c\n    field f integer u8\n    presence f_present"), 4);
+        assert_eq!(syntax_error_line("provider p\n  class c\n    field f optional bool\n    presence g_present"), 4);
+    }
+
+    #[test]
+    fn refuses_to_write_enums() {
+        let mut provider = Provider::new("p");
+        provider.create_class("c").add_field("e", CTFType::Enum);
+        assert!(write(&mut Vec::new(), &[provider]).is_err());
+    }
+}
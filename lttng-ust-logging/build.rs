@@ -13,7 +13,7 @@ fn main() {
             .add_field("line", CTFType::Integer(CIntegerType::U32))
             .add_field("module_path", CTFType::SequenceText)
             .add_field("target", CTFType::SequenceText)
-            .add_field("message", CTFType::SequenceText);
+            .add_field("message", CTFType::Display);
 
         log_entry_class.instantiate_with_level("trace",LogLevel::Debug);
         log_entry_class.instantiate_with_level("debug",LogLevel::DebugLine);
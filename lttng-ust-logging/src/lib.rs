@@ -32,14 +32,15 @@ impl log::Log for LTTNGLogger {
         let line = record.line().unwrap_or(0);
         let module_path = record.module_path().unwrap_or("<unknown>");
         let target = record.target();
-        let msg = format!("{}", record.args());
+        // Only formatted if a session is actually recording the event
+        let msg = record.args();
 
         match record.level() {
-            Level::Error => error(file, line, module_path, target, &msg),
-            Level::Warn => warn(file, line, module_path, target, &msg),
-            Level::Info => info(file, line, module_path, target, &msg),
-            Level::Debug => debug(file, line, module_path, target, &msg),
-            Level::Trace => trace(file, line, module_path, target, &msg),
+            Level::Error => error(file, line, module_path, target, msg),
+            Level::Warn => warn(file, line, module_path, target, msg),
+            Level::Info => info(file, line, module_path, target, msg),
+            Level::Debug => debug(file, line, module_path, target, msg),
+            Level::Trace => trace(file, line, module_path, target, msg),
         }
     }
 
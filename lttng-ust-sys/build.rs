@@ -0,0 +1,75 @@
+use std::env;
+use std::path::PathBuf;
+
+extern crate cc;
+
+fn main() {
+    // Checked before anything looks for headers, so this is the error people get
+    if cfg!(all(feature = "helpers", feature = "v2_13")) {
+        panic!("the helpers feature of lttng-ust-sys only supports lttng-ust 2.12, \
+                so it can't be combined with v2_13");
+    }
+    println!("cargo:rerun-if-env-changed=LTTNG_UST_LIB_DIR");
+    if let Some(dir) = env::var_os("LTTNG_UST_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir.to_string_lossy());
+    }
+    check_layout();
+    #[cfg(feature = "helpers")]
+    compile_helpers();
+    println!("cargo:rerun-if-env-changed=LTTNG_UST_STATIC");
+    let is_static = env::var_os("LTTNG_UST_STATIC").is_some_and(|value| value != "0");
+    // Seen as DEP_LTTNG_UST_STATIC by lttng-ust-generate, so tracepoint libraries link the same way
+    println!("cargo:static={}", if is_static { 1 } else { 0 });
+    if is_static {
+        println!("cargo:rustc-link-lib=static=lttng-ust");
+        // lttng-ust looks up its optional libraries with dlopen
+        println!("cargo:rustc-link-lib=dl");
+    } else {
+        println!("cargo:rustc-link-lib=dylib=lttng-ust");
+    }
+}
+
+/// Finds the directory holding `lttng/tracef.h`: `LTTNG_UST_INCLUDE_DIR`, or the system include
+/// directories when building for the host.
+fn lttng_ust_include_dir() -> Option<PathBuf> {
+    let mut candidates = Vec::new();
+    if let Some(dir) = env::var_os("LTTNG_UST_INCLUDE_DIR") {
+        candidates.push(PathBuf::from(dir));
+    } else if env::var("TARGET").ok() == env::var("HOST").ok() {
+        candidates.push(PathBuf::from("/usr/local/include"));
+        candidates.push(PathBuf::from("/usr/include"));
+    }
+    candidates.into_iter().find(|dir| dir.join("lttng/tracef.h").is_file())
+}
+
+/// Compiles `src/layout.c`, which statically asserts that the link names and struct layouts
+/// in the bindings match the lttng-ust headers. Skipped when the headers can't be found.
+fn check_layout() {
+    println!("cargo:rerun-if-changed=src/layout.c");
+    println!("cargo:rerun-if-env-changed=LTTNG_UST_INCLUDE_DIR");
+    let dir = match lttng_ust_include_dir() {
+        Some(dir) => dir,
+        None => return,
+    };
+    let v2_13 = cfg!(feature = "v2_13");
+    let result = cc::Build::new()
+        .file("src/layout.c")
+        .include(&dir)
+        .define("LTTNG_UST_SYS_V2_13", if v2_13 { "1" } else { "0" })
+        .cargo_metadata(false)
+        .try_compile_intermediates();
+    if let Err(e) = result {
+        panic!("the bindings don't match the lttng-ust headers in {}: {}", dir.display(), e);
+    }
+}
+
+#[cfg(feature = "helpers")]
+fn compile_helpers() {
+    println!("cargo:rerun-if-changed=src/helpers.c");
+    let mut build = cc::Build::new();
+    build.file("src/helpers.c");
+    if let Some(dir) = env::var_os("LTTNG_UST_INCLUDE_DIR") {
+        build.include(dir);
+    }
+    build.compile("lttng-ust-sys-helpers");
+}
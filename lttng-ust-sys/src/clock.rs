@@ -0,0 +1,44 @@
+//! The clock override plugin interface, from `lttng/ust-clock.h`.
+//!
+//! A clock plugin is a shared object named by the `LTTNG_UST_CLOCK_PLUGIN` environment
+//! variable. lttng-ust calls its `lttng_ust_clock_plugin_init` function on startup, which
+//! installs the callbacks below and then calls `lttng_ust_enable_trace_clock_override`.
+//! All functions return 0 on success, or a negative errno value.
+
+use core::ffi::{c_char, c_int};
+
+extern "C" {
+    /// Read the current time, in clock cycles
+    pub fn lttng_ust_trace_clock_set_read64_cb(read64: Option<unsafe extern "C" fn() -> u64>) -> c_int;
+
+    /// Get the current `read64` callback
+    pub fn lttng_ust_trace_clock_get_read64_cb(read64: *mut Option<unsafe extern "C" fn() -> u64>) -> c_int;
+
+    /// Frequency of the clock, in Hz
+    pub fn lttng_ust_trace_clock_set_freq_cb(freq: Option<unsafe extern "C" fn() -> u64>) -> c_int;
+
+    /// Get the current `freq` callback
+    pub fn lttng_ust_trace_clock_get_freq_cb(freq: *mut Option<unsafe extern "C" fn() -> u64>) -> c_int;
+
+    /// Write the UUID of the clock, as a 37 byte NUL terminated string, into `uuid`.
+    /// The callback returns 0 on success.
+    pub fn lttng_ust_trace_clock_set_uuid_cb(uuid: Option<unsafe extern "C" fn(uuid: *mut c_char) -> c_int>) -> c_int;
+
+    /// Get the current `uuid` callback
+    pub fn lttng_ust_trace_clock_get_uuid_cb(uuid: *mut Option<unsafe extern "C" fn(uuid: *mut c_char) -> c_int>) -> c_int;
+
+    /// Name of the clock
+    pub fn lttng_ust_trace_clock_set_name_cb(name: Option<unsafe extern "C" fn() -> *const c_char>) -> c_int;
+
+    /// Get the current `name` callback
+    pub fn lttng_ust_trace_clock_get_name_cb(name: *mut Option<unsafe extern "C" fn() -> *const c_char>) -> c_int;
+
+    /// Description of the clock
+    pub fn lttng_ust_trace_clock_set_description_cb(description: Option<unsafe extern "C" fn() -> *const c_char>) -> c_int;
+
+    /// Get the current `description` callback
+    pub fn lttng_ust_trace_clock_get_description_cb(description: *mut Option<unsafe extern "C" fn() -> *const c_char>) -> c_int;
+
+    /// Switch to the callbacks installed above
+    pub fn lttng_ust_enable_trace_clock_override() -> c_int;
+}
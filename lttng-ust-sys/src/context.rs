@@ -0,0 +1,151 @@
+//! Application context providers, from `lttng/ust-context-provider.h`.
+//!
+//! A provider named `$app.<name>` supplies the values of the `$app.<name>:<context>` contexts
+//! added with `lttng add-context -u -t`. `get_value` is used by filters, while `get_size` and
+//! `record` write the value into the ring buffer, which needs the inline helpers of
+//! `lttng/ringbuffer-config.h`. The `helpers` feature provides those as
+//! [`helpers`](../helpers/index.html).
+
+use core::ffi::{c_char, c_double, c_int};
+#[cfg(feature = "v2_13")]
+use core::ffi::c_void;
+
+/// `enum lttng_ust_dynamic_type`, selecting the member of [`lttng_ctx_value::u`]
+pub type lttng_ust_dynamic_type = c_int;
+/// No value
+pub const LTTNG_UST_DYNAMIC_TYPE_NONE: lttng_ust_dynamic_type = 0;
+/// `int8_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_S8: lttng_ust_dynamic_type = 1;
+/// `int16_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_S16: lttng_ust_dynamic_type = 2;
+/// `int32_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_S32: lttng_ust_dynamic_type = 3;
+/// `int64_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_S64: lttng_ust_dynamic_type = 4;
+/// `uint8_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_U8: lttng_ust_dynamic_type = 5;
+/// `uint16_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_U16: lttng_ust_dynamic_type = 6;
+/// `uint32_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_U32: lttng_ust_dynamic_type = 7;
+/// `uint64_t`
+pub const LTTNG_UST_DYNAMIC_TYPE_U64: lttng_ust_dynamic_type = 8;
+/// `float`
+pub const LTTNG_UST_DYNAMIC_TYPE_FLOAT: lttng_ust_dynamic_type = 9;
+/// `double`
+pub const LTTNG_UST_DYNAMIC_TYPE_DOUBLE: lttng_ust_dynamic_type = 10;
+/// NUL terminated string
+pub const LTTNG_UST_DYNAMIC_TYPE_STRING: lttng_ust_dynamic_type = 11;
+
+/// The value of a context, as seen by filters
+#[repr(C)]
+#[derive(Copy,Clone)]
+pub union lttng_ctx_value_u {
+    /// Signed integers
+    pub s64: i64,
+    /// Unsigned integers
+    #[cfg(feature = "v2_13")]
+    pub u64: u64,
+    /// Strings
+    pub str: *const c_char,
+    /// Floating point numbers
+    pub d: c_double,
+}
+
+/// `struct lttng_ctx_value` (`struct lttng_ust_ctx_value` in 2.13)
+#[repr(C)]
+#[derive(Copy,Clone)]
+pub struct lttng_ctx_value {
+    /// Size of the structure, for ABI extensions
+    #[cfg(feature = "v2_13")]
+    pub struct_size: u32,
+    /// Which member of `u` is set
+    pub sel: lttng_ust_dynamic_type,
+    /// The value
+    pub u: lttng_ctx_value_u,
+}
+
+/// `struct lttng_ctx_field`, the context being evaluated. Its name, available through
+/// [`lttng_ust_sys_ctx_field_name`](../helpers/fn.lttng_ust_sys_ctx_field_name.html), is
+/// `$app.<provider>:<context>`.
+#[cfg(not(feature = "v2_13"))]
+pub enum lttng_ctx_field {}
+
+/// `struct lttng_ust_lib_ring_buffer_ctx` (`struct lttng_ust_ring_buffer_ctx` in 2.13)
+pub enum lttng_ust_lib_ring_buffer_ctx {}
+
+/// `struct lttng_channel` (`struct lttng_ust_channel_buffer` in 2.13)
+pub enum lttng_channel {}
+
+/// `struct cds_hlist_node`, from liburcu
+#[cfg(not(feature = "v2_13"))]
+#[repr(C)]
+pub struct cds_hlist_node {
+    /// Next node
+    pub next: *mut cds_hlist_node,
+    /// The `next` pointer pointing to this node
+    pub pprev: *mut *mut cds_hlist_node,
+}
+
+/// `struct lttng_ust_context_provider`. It must stay alive and in place while registered.
+#[cfg(not(feature = "v2_13"))]
+#[repr(C)]
+pub struct lttng_ust_context_provider {
+    /// `$app.<name>`
+    pub name: *mut c_char,
+    /// Space needed to record the context at `offset` in the ring buffer, alignment included
+    pub get_size: Option<unsafe extern "C" fn(field: *mut lttng_ctx_field, offset: usize) -> usize>,
+    /// Write the context into the ring buffer
+    pub record: Option<unsafe extern "C" fn(field: *mut lttng_ctx_field,
+                                            ctx: *mut lttng_ust_lib_ring_buffer_ctx,
+                                            chan: *mut lttng_channel)>,
+    /// Fill in the value of the context for filters
+    pub get_value: Option<unsafe extern "C" fn(field: *mut lttng_ctx_field, value: *mut lttng_ctx_value)>,
+    /// Used by lttng-ust while registered
+    pub node: cds_hlist_node,
+}
+
+/// `struct lttng_ust_context_provider`. It must stay alive and in place while registered.
+#[cfg(feature = "v2_13")]
+#[repr(C)]
+pub struct lttng_ust_context_provider {
+    /// Size of the structure, for ABI extensions
+    pub struct_size: u32,
+    /// `$app.<name>`
+    pub name: *const c_char,
+    /// Space needed to record the context at `offset` in the ring buffer, alignment included
+    pub get_size: Option<unsafe extern "C" fn(priv_: *mut c_void, offset: usize) -> usize>,
+    /// Write the context into the ring buffer
+    pub record: Option<unsafe extern "C" fn(priv_: *mut c_void,
+                                            ctx: *mut lttng_ust_lib_ring_buffer_ctx,
+                                            chan: *mut lttng_channel)>,
+    /// Fill in the value of the context for filters
+    pub get_value: Option<unsafe extern "C" fn(priv_: *mut c_void, value: *mut lttng_ctx_value)>,
+    /// Passed to the callbacks
+    pub priv_: *mut c_void,
+}
+
+/// `struct lttng_ust_registered_context_provider`, returned by registration
+#[cfg(feature = "v2_13")]
+pub enum lttng_ust_registered_context_provider {}
+
+extern "C" {
+    /// Register `provider`. Returns 0 on success, or a negative errno value, e.g. if a
+    /// provider with the same name is already registered.
+    #[cfg(not(feature = "v2_13"))]
+    pub fn lttng_ust_context_provider_register(provider: *mut lttng_ust_context_provider) -> c_int;
+
+    /// Unregister `provider`
+    #[cfg(not(feature = "v2_13"))]
+    pub fn lttng_ust_context_provider_unregister(provider: *mut lttng_ust_context_provider);
+
+    /// Register `provider`. Returns null on failure, e.g. if a provider with the same name is
+    /// already registered.
+    #[cfg(feature = "v2_13")]
+    pub fn lttng_ust_context_provider_register(provider: *mut lttng_ust_context_provider)
+        -> *mut lttng_ust_registered_context_provider;
+
+    /// Unregister a provider
+    #[cfg(feature = "v2_13")]
+    pub fn lttng_ust_context_provider_unregister(provider: *mut lttng_ust_registered_context_provider);
+}
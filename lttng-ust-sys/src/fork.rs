@@ -0,0 +1,28 @@
+//! Fork hooks, from `lttng/ust.h`.
+//!
+//! Programs calling the `fork()` system call directly (rather than through libc, which
+//! `liblttng-ust-fork.so` wraps) must call these around it to keep tracing working in the
+//! child.
+
+use core::ffi::c_ulong;
+use core::mem;
+
+/// `sigset_t`, as laid out by glibc and musl
+#[repr(C)]
+pub struct sigset_t {
+    __val: [c_ulong; 1024 / (8 * mem::size_of::<c_ulong>())],
+}
+
+extern "C" {
+    /// Call right before forking. Blocks signals, saving the previous mask in `save_sigset`.
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_before_fork")]
+    pub fn ust_before_fork(save_sigset: *mut sigset_t);
+
+    /// Call in the parent after forking, with the mask saved by `ust_before_fork`
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_after_fork_parent")]
+    pub fn ust_after_fork_parent(restore_sigset: *mut sigset_t);
+
+    /// Call in the child after forking, with the mask saved by `ust_before_fork`
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_after_fork_child")]
+    pub fn ust_after_fork_child(restore_sigset: *mut sigset_t);
+}
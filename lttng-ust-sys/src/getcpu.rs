@@ -0,0 +1,13 @@
+//! The getcpu override plugin interface, from `lttng/ust-getcpu.h`.
+//!
+//! A getcpu plugin is a shared object named by the `LTTNG_UST_GETCPU_PLUGIN` environment
+//! variable. lttng-ust calls its `lttng_ust_getcpu_plugin_init` function on startup, which
+//! installs the callback below.
+
+use core::ffi::c_int;
+
+extern "C" {
+    /// Use `getcpu` to find out which CPU the calling thread runs on, i.e. which per-CPU
+    /// buffer events go to. Returns 0 on success, or a negative errno value.
+    pub fn lttng_ust_getcpu_override(getcpu: Option<unsafe extern "C" fn() -> c_int>) -> c_int;
+}
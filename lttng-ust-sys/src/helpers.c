@@ -0,0 +1,34 @@
+/*
+ * Out-of-line copies of the inline lttng-ust helpers application context providers need to
+ * write into the ring buffer, which can't be called from Rust directly.
+ */
+#include <stddef.h>
+
+#include <lttng/ust-version.h>
+#include <lttng/ust-events.h>
+#include <lttng/ringbuffer-config.h>
+
+#if LTTNG_UST_MAJOR_VERSION != 2 || LTTNG_UST_MINOR_VERSION != 12
+#error "the helpers of lttng-ust-sys need the lttng-ust 2.12 headers"
+#endif
+
+const char *lttng_ust_sys_ctx_field_name(struct lttng_ctx_field *field)
+{
+	return field->event_field.name;
+}
+
+size_t lttng_ust_sys_ring_buffer_align(size_t offset, size_t alignment)
+{
+	return lib_ring_buffer_align(offset, alignment);
+}
+
+void lttng_ust_sys_ring_buffer_align_ctx(struct lttng_ust_lib_ring_buffer_ctx *ctx, size_t alignment)
+{
+	lib_ring_buffer_align_ctx(ctx, alignment);
+}
+
+void lttng_ust_sys_event_write(struct lttng_channel *chan, struct lttng_ust_lib_ring_buffer_ctx *ctx,
+		const void *src, size_t len)
+{
+	chan->ops->event_write(ctx, src, len);
+}
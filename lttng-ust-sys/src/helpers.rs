@@ -0,0 +1,27 @@
+//! Out-of-line copies of inline helpers from the lttng-ust headers, compiled from
+//! `src/helpers.c` with the `helpers` feature.
+//!
+//! They are what the `get_size` and `record` callbacks of a
+//! [`lttng_ust_context_provider`](../context/struct.lttng_ust_context_provider.html) need to
+//! find out which context they're called for and write its value. Only lttng-ust 2.12 is
+//! supported.
+
+use core::ffi::{c_char, c_void};
+
+use context::{lttng_channel, lttng_ctx_field, lttng_ust_lib_ring_buffer_ctx};
+
+extern "C" {
+    /// The name of `field`, `$app.<provider>:<context>` for application contexts
+    pub fn lttng_ust_sys_ctx_field_name(field: *mut lttng_ctx_field) -> *const c_char;
+
+    /// Padding needed at `offset` for a value aligned on `alignment`, i.e.
+    /// `lib_ring_buffer_align`. It is 0 on architectures where the ring buffer isn't aligned.
+    pub fn lttng_ust_sys_ring_buffer_align(offset: usize, alignment: usize) -> usize;
+
+    /// Align the write position of `ctx` on `alignment`, i.e. `lib_ring_buffer_align_ctx`
+    pub fn lttng_ust_sys_ring_buffer_align_ctx(ctx: *mut lttng_ust_lib_ring_buffer_ctx, alignment: usize);
+
+    /// Write `len` bytes from `src` at the position of `ctx`, i.e. `chan->ops->event_write`
+    pub fn lttng_ust_sys_event_write(chan: *mut lttng_channel, ctx: *mut lttng_ust_lib_ring_buffer_ctx,
+                                     src: *const c_void, len: usize);
+}
@@ -0,0 +1,124 @@
+/*
+ * Compile-time checks of the Rust bindings against the installed lttng-ust headers. Nothing
+ * here is linked; it only has to compile. Built by build.rs whenever the headers are found,
+ * with LTTNG_UST_SYS_V2_13 defined for the v2_13 feature.
+ */
+#define _GNU_SOURCE
+#include <signal.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#include <lttng/ust-version.h>
+#include <lttng/tracef.h>
+#include <lttng/tracelog.h>
+#if __has_include(<lttng/ust-fork.h>)
+#include <lttng/ust-fork.h>
+#else
+#include <lttng/ust.h>
+#endif
+
+#if LTTNG_UST_SYS_V2_13
+#if LTTNG_UST_MAJOR_VERSION != 2 || LTTNG_UST_MINOR_VERSION < 13
+#error "the v2_13 feature of lttng-ust-sys needs the lttng-ust 2.13 headers"
+#endif
+#else
+#if LTTNG_UST_MAJOR_VERSION != 2 || LTTNG_UST_MINOR_VERSION != 12
+#error "lttng-ust-sys binds lttng-ust 2.12 by default; enable the v2_13 feature for newer headers"
+#endif
+#endif
+
+#define CHECK(cond, what) _Static_assert(cond, "lttng-ust-sys: " what " doesn't match the headers")
+#define CHECK_TYPE(symbol, type) CHECK(__builtin_types_compatible_p(__typeof__(symbol), type), #symbol)
+
+/* The link names in tracef.rs and fork.rs */
+#if LTTNG_UST_SYS_V2_13
+#define TRACEF_TP lttng_ust_tracepoint_lttng_ust_tracef___event
+#define TRACEF_FN lttng_ust__tracef
+#define TRACELOG_TP(level) lttng_ust_tracepoint_lttng_ust_tracelog___##level
+#define TRACELOG_FN(level) lttng_ust__tracelog_##level
+#define BEFORE_FORK lttng_ust_before_fork
+#define AFTER_FORK_PARENT lttng_ust_after_fork_parent
+#define AFTER_FORK_CHILD lttng_ust_after_fork_child
+#else
+#define TRACEF_TP __tracepoint_lttng_ust_tracef___event
+#define TRACEF_FN _lttng_ust_tracef
+#define TRACELOG_TP(level) __tracepoint_lttng_ust_tracelog___##level
+#define TRACELOG_FN(level) _lttng_ust_tracelog_##level
+#define BEFORE_FORK ust_before_fork
+#define AFTER_FORK_PARENT ust_after_fork_parent
+#define AFTER_FORK_CHILD ust_after_fork_child
+#endif
+
+/* `tracepoint` in tracepoint.rs */
+struct rust_tracepoint {
+#if LTTNG_UST_SYS_V2_13
+	uint32_t struct_size;
+	const char *provider_name;
+	const char *event_name;
+#else
+	const char *name;
+#endif
+	int state;
+	void *probes;
+	int *tracepoint_provider_ref;
+	const char *signature;
+#if !LTTNG_UST_SYS_V2_13
+	char padding[16];
+#endif
+};
+
+typedef __typeof__(TRACEF_TP) c_tracepoint;
+CHECK(sizeof(struct rust_tracepoint) == sizeof(c_tracepoint), "the size of tracepoint");
+CHECK(_Alignof(struct rust_tracepoint) == _Alignof(c_tracepoint), "the alignment of tracepoint");
+CHECK(offsetof(struct rust_tracepoint, state) == offsetof(c_tracepoint, state), "tracepoint.state");
+CHECK(offsetof(struct rust_tracepoint, signature) == offsetof(c_tracepoint, signature),
+      "tracepoint.signature");
+
+CHECK_TYPE(&TRACEF_FN, void (*)(const char *, ...));
+
+/* Takes the level without its TRACE_ prefix, which 2.13 defines as a macro */
+#define CHECK_TRACELOG(level)							\
+	CHECK((int) TRACE_##level == LTTNG_UST_SYS_TRACE_##level, "TRACE_" #level); \
+	CHECK_TYPE(&TRACELOG_TP(TRACE_##level), c_tracepoint *);		\
+	CHECK_TYPE(&TRACELOG_FN(TRACE_##level), void (*)(const char *, int, const char *, const char *, ...))
+
+/* The log levels in tracef.rs */
+enum {
+	LTTNG_UST_SYS_TRACE_EMERG = 0,
+	LTTNG_UST_SYS_TRACE_ALERT = 1,
+	LTTNG_UST_SYS_TRACE_CRIT = 2,
+	LTTNG_UST_SYS_TRACE_ERR = 3,
+	LTTNG_UST_SYS_TRACE_WARNING = 4,
+	LTTNG_UST_SYS_TRACE_NOTICE = 5,
+	LTTNG_UST_SYS_TRACE_INFO = 6,
+	LTTNG_UST_SYS_TRACE_DEBUG_SYSTEM = 7,
+	LTTNG_UST_SYS_TRACE_DEBUG_PROGRAM = 8,
+	LTTNG_UST_SYS_TRACE_DEBUG_PROCESS = 9,
+	LTTNG_UST_SYS_TRACE_DEBUG_MODULE = 10,
+	LTTNG_UST_SYS_TRACE_DEBUG_UNIT = 11,
+	LTTNG_UST_SYS_TRACE_DEBUG_FUNCTION = 12,
+	LTTNG_UST_SYS_TRACE_DEBUG_LINE = 13,
+	LTTNG_UST_SYS_TRACE_DEBUG = 14,
+};
+
+CHECK_TRACELOG(EMERG);
+CHECK_TRACELOG(ALERT);
+CHECK_TRACELOG(CRIT);
+CHECK_TRACELOG(ERR);
+CHECK_TRACELOG(WARNING);
+CHECK_TRACELOG(NOTICE);
+CHECK_TRACELOG(INFO);
+CHECK_TRACELOG(DEBUG_SYSTEM);
+CHECK_TRACELOG(DEBUG_PROGRAM);
+CHECK_TRACELOG(DEBUG_PROCESS);
+CHECK_TRACELOG(DEBUG_MODULE);
+CHECK_TRACELOG(DEBUG_UNIT);
+CHECK_TRACELOG(DEBUG_FUNCTION);
+CHECK_TRACELOG(DEBUG_LINE);
+CHECK_TRACELOG(DEBUG);
+
+/* `sigset_t` in fork.rs holds 1024 bits */
+CHECK(sizeof(sigset_t) == 1024 / 8, "the size of sigset_t");
+CHECK_TYPE(&BEFORE_FORK, void (*)(sigset_t *));
+CHECK_TYPE(&AFTER_FORK_PARENT, void (*)(sigset_t *));
+CHECK_TYPE(&AFTER_FORK_CHILD, void (*)(sigset_t *));
@@ -0,0 +1,39 @@
+//! Raw bindings to the public C API of `liblttng-ust`.
+//!
+//! These cover what can be used without generated tracepoint providers: `tracef()` and
+//! `tracelog()`, checking whether a tracepoint is enabled, the fork hooks, application context
+//! providers, probe registration and the clock and getcpu plugin interfaces. Everything here
+//! is `unsafe` and mirrors the C headers closely; see the
+//! [lttng-ust documentation](https://lttng.org/man/3/lttng-ust/) for how to use it.
+//!
+//! The bindings follow the lttng-ust version selected by the `v2_12` (default) or `v2_13`
+//! feature. Functions and statics whose signature didn't change keep their 2.12 names, and
+//! link to the renamed 2.13 symbols with `v2_13`. The others only exist in the version that
+//! has them.
+//!
+//! The build script links `liblttng-ust` dynamically, or statically if `LTTNG_UST_STATIC` is
+//! set (to anything but `0`), looking in `LTTNG_UST_LIB_DIR` first if it is set. It publishes
+//! the choice as `DEP_LTTNG_UST_STATIC`, which `lttng-ust-generate` follows. The `helpers`
+//! feature also compiles the [`helpers`](helpers/index.html) shim against the lttng-ust
+//! headers, found in `LTTNG_UST_INCLUDE_DIR` if they aren't installed system-wide. It only
+//! supports lttng-ust 2.12, and the build script fails if it is combined with `v2_13`.
+//!
+//! Whenever the headers can be found, the build script also compiles a C file of static
+//! assertions against them, so a link name, log level or struct layout here that doesn't match
+//! the selected lttng-ust version fails the build instead of misbehaving at runtime.
+#![no_std]
+#![allow(non_camel_case_types)]
+#![deny(missing_docs)]
+
+#[cfg(not(any(feature = "v2_12", feature = "v2_13")))]
+compile_error!("lttng-ust-sys needs either the v2_12 or the v2_13 feature");
+
+pub mod clock;
+pub mod context;
+pub mod fork;
+pub mod getcpu;
+#[cfg(feature = "helpers")]
+pub mod helpers;
+pub mod probe;
+pub mod tracef;
+pub mod tracepoint;
@@ -0,0 +1,38 @@
+//! Probe registration, from `lttng/ust-events.h`.
+//!
+//! Tracepoint providers register their probes from a constructor, which the code generated
+//! by `TRACEPOINT_CREATE_PROBES` does on its own. The descriptors are built by those macros
+//! and are opaque here.
+
+#[cfg(not(feature = "v2_13"))]
+use core::ffi::c_int;
+
+/// `struct lttng_probe_desc`, describing the events of a provider
+#[cfg(not(feature = "v2_13"))]
+pub enum lttng_probe_desc {}
+
+/// `struct lttng_ust_probe_desc`, describing the events of a provider
+#[cfg(feature = "v2_13")]
+pub enum lttng_ust_probe_desc {}
+
+/// `struct lttng_ust_registered_probe`, returned by registration
+#[cfg(feature = "v2_13")]
+pub enum lttng_ust_registered_probe {}
+
+extern "C" {
+    /// Register the probes of a provider. Returns 0 on success, or a negative errno value.
+    #[cfg(not(feature = "v2_13"))]
+    pub fn lttng_probe_register(desc: *mut lttng_probe_desc) -> c_int;
+
+    /// Unregister the probes of a provider
+    #[cfg(not(feature = "v2_13"))]
+    pub fn lttng_probe_unregister(desc: *mut lttng_probe_desc);
+
+    /// Register the probes of a provider. Returns null on failure.
+    #[cfg(feature = "v2_13")]
+    pub fn lttng_ust_probe_register(desc: *const lttng_ust_probe_desc) -> *mut lttng_ust_registered_probe;
+
+    /// Unregister the probes of a provider
+    #[cfg(feature = "v2_13")]
+    pub fn lttng_ust_probe_unregister(reg_probe: *mut lttng_ust_registered_probe);
+}
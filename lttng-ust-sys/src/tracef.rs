@@ -0,0 +1,184 @@
+//! `tracef()` and `tracelog()`, from `lttng/tracef.h` and `lttng/tracelog.h`.
+//!
+//! Both are macros in C, which check the state of the `lttng_ust_tracef:event` or
+//! `lttng_ust_tracelog:<level>` tracepoint before calling the function doing the actual work.
+
+use core::ffi::{c_char, c_int};
+
+use tracepoint::tracepoint;
+
+/// `TRACE_EMERG` log level
+pub const TRACE_EMERG: c_int = 0;
+/// `TRACE_ALERT` log level
+pub const TRACE_ALERT: c_int = 1;
+/// `TRACE_CRIT` log level
+pub const TRACE_CRIT: c_int = 2;
+/// `TRACE_ERR` log level
+pub const TRACE_ERR: c_int = 3;
+/// `TRACE_WARNING` log level
+pub const TRACE_WARNING: c_int = 4;
+/// `TRACE_NOTICE` log level
+pub const TRACE_NOTICE: c_int = 5;
+/// `TRACE_INFO` log level
+pub const TRACE_INFO: c_int = 6;
+/// `TRACE_DEBUG_SYSTEM` log level
+pub const TRACE_DEBUG_SYSTEM: c_int = 7;
+/// `TRACE_DEBUG_PROGRAM` log level
+pub const TRACE_DEBUG_PROGRAM: c_int = 8;
+/// `TRACE_DEBUG_PROCESS` log level
+pub const TRACE_DEBUG_PROCESS: c_int = 9;
+/// `TRACE_DEBUG_MODULE` log level
+pub const TRACE_DEBUG_MODULE: c_int = 10;
+/// `TRACE_DEBUG_UNIT` log level
+pub const TRACE_DEBUG_UNIT: c_int = 11;
+/// `TRACE_DEBUG_FUNCTION` log level
+pub const TRACE_DEBUG_FUNCTION: c_int = 12;
+/// `TRACE_DEBUG_LINE` log level
+pub const TRACE_DEBUG_LINE: c_int = 13;
+/// `TRACE_DEBUG` log level
+pub const TRACE_DEBUG: c_int = 14;
+
+extern "C" {
+    /// The `lttng_ust_tracef:event` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracef___event")]
+    pub static __tracepoint_lttng_ust_tracef___event: tracepoint;
+
+    /// Record a `lttng_ust_tracef:event` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracef")]
+    pub fn _lttng_ust_tracef(fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_EMERG` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_EMERG")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_EMERG: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_EMERG` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_EMERG")]
+    pub fn _lttng_ust_tracelog_TRACE_EMERG(file: *const c_char, line: c_int, func: *const c_char,
+                                           fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_ALERT` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_ALERT")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_ALERT: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_ALERT` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_ALERT")]
+    pub fn _lttng_ust_tracelog_TRACE_ALERT(file: *const c_char, line: c_int, func: *const c_char,
+                                           fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_CRIT` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_CRIT")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_CRIT: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_CRIT` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_CRIT")]
+    pub fn _lttng_ust_tracelog_TRACE_CRIT(file: *const c_char, line: c_int, func: *const c_char,
+                                          fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_ERR` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_ERR")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_ERR: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_ERR` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_ERR")]
+    pub fn _lttng_ust_tracelog_TRACE_ERR(file: *const c_char, line: c_int, func: *const c_char,
+                                         fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_WARNING` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_WARNING")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_WARNING: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_WARNING` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_WARNING")]
+    pub fn _lttng_ust_tracelog_TRACE_WARNING(file: *const c_char, line: c_int, func: *const c_char,
+                                             fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_NOTICE` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_NOTICE")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_NOTICE: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_NOTICE` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_NOTICE")]
+    pub fn _lttng_ust_tracelog_TRACE_NOTICE(file: *const c_char, line: c_int, func: *const c_char,
+                                            fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_INFO` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_INFO")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_INFO: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_INFO` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_INFO")]
+    pub fn _lttng_ust_tracelog_TRACE_INFO(file: *const c_char, line: c_int, func: *const c_char,
+                                          fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_SYSTEM` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_SYSTEM")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_SYSTEM: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_SYSTEM` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_SYSTEM")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_SYSTEM(file: *const c_char, line: c_int, func: *const c_char,
+                                                  fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_PROGRAM` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_PROGRAM")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_PROGRAM: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_PROGRAM` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_PROGRAM")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_PROGRAM(file: *const c_char, line: c_int, func: *const c_char,
+                                                   fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_PROCESS` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_PROCESS")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_PROCESS: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_PROCESS` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_PROCESS")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_PROCESS(file: *const c_char, line: c_int, func: *const c_char,
+                                                   fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_MODULE` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_MODULE")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_MODULE: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_MODULE` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_MODULE")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_MODULE(file: *const c_char, line: c_int, func: *const c_char,
+                                                  fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_UNIT` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_UNIT")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_UNIT: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_UNIT` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_UNIT")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_UNIT(file: *const c_char, line: c_int, func: *const c_char,
+                                                fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_FUNCTION` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_FUNCTION")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_FUNCTION: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_FUNCTION` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_FUNCTION")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_FUNCTION(file: *const c_char, line: c_int, func: *const c_char,
+                                                    fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG_LINE` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG_LINE")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_LINE: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG_LINE` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG_LINE")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG_LINE(file: *const c_char, line: c_int, func: *const c_char,
+                                                fmt: *const c_char, ...);
+
+    /// The `lttng_ust_tracelog:TRACE_DEBUG` tracepoint
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust_tracepoint_lttng_ust_tracelog___TRACE_DEBUG")]
+    pub static __tracepoint_lttng_ust_tracelog___TRACE_DEBUG: tracepoint;
+
+    /// Record a `lttng_ust_tracelog:TRACE_DEBUG` event with a printf-style message
+    #[cfg_attr(feature = "v2_13", link_name = "lttng_ust__tracelog_TRACE_DEBUG")]
+    pub fn _lttng_ust_tracelog_TRACE_DEBUG(file: *const c_char, line: c_int, func: *const c_char,
+                                           fmt: *const c_char, ...);
+}
@@ -0,0 +1,44 @@
+//! Tracepoint state, from `lttng/tracepoint.h`
+
+use core::ffi::{c_char, c_int, c_void};
+use core::ptr;
+use core::sync::atomic::{AtomicI32, Ordering};
+
+/// `struct tracepoint` (`struct lttng_ust_tracepoint` in 2.13), of which lttng-ust defines one
+/// per tracepoint. `state` is nonzero while a tracing session records the tracepoint.
+#[repr(C)]
+pub struct tracepoint {
+    /// Size of the structure, for ABI extensions
+    #[cfg(feature = "v2_13")]
+    pub struct_size: u32,
+    /// Name of the provider
+    #[cfg(feature = "v2_13")]
+    pub provider_name: *const c_char,
+    /// Name of the event
+    #[cfg(feature = "v2_13")]
+    pub event_name: *const c_char,
+    /// `provider:event`
+    #[cfg(not(feature = "v2_13"))]
+    pub name: *const c_char,
+    /// Nonzero while the tracepoint is enabled
+    pub state: c_int,
+    /// Probes attached to the tracepoint
+    pub probes: *mut c_void,
+    /// Reference count of the tracepoint provider
+    pub tracepoint_provider_ref: *mut c_int,
+    /// Signature of the probes, used to check them against the tracepoint
+    pub signature: *const c_char,
+    /// Reserved
+    #[cfg(not(feature = "v2_13"))]
+    pub padding: [c_char; 16],
+}
+
+/// Whether `tp` is enabled, like the `tracepoint_enabled()` macro. lttng-ust updates `state`
+/// from its own threads, so it is loaded atomically.
+///
+/// # Safety
+/// `tp` must point to a tracepoint defined by lttng-ust or a tracepoint provider.
+#[inline]
+pub unsafe fn tracepoint_enabled(tp: *const tracepoint) -> bool {
+    (*ptr::addr_of!((*tp).state).cast::<AtomicI32>()).load(Ordering::Relaxed) != 0
+}
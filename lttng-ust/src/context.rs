@@ -0,0 +1,568 @@
+//! Application context providers, which add values computed by Rust closures to every event
+//! of a tracing session without changing any tracepoint:
+//!
+//! ```no_run
+//! # extern crate lttng_ust;
+//! use std::cell::Cell;
+//! use lttng_ust::context::{ContextProvider, ContextValue};
+//!
+//! thread_local!(static REQUEST_ID: Cell<u64> = Cell::new(0));
+//!
+//! # fn main() {
+//! let _provider = ContextProvider::new("myapp")
+//!     .context("request_id", || REQUEST_ID.with(|id| id.get()).into())
+//!     .context("tenant", || ContextValue::from("acme"))
+//!     .register()
+//!     .expect("another provider is already named myapp");
+//! # }
+//! ```
+//!
+//! The contexts are then added to a session by name, and can be used in filters:
+//!
+//! ```text
+//! lttng add-context -u -t '$app.myapp:request_id' -t '$app.myapp:tenant'
+//! lttng enable-event -u -a --filter '$app.myapp:tenant == "acme"'
+//! ```
+//!
+//! The closures run on the thread firing the event, every time an event is recorded or a
+//! filter needs the value, so they should be cheap. Contexts the provider doesn't know about
+//! have no value.
+//!
+//! Only available with the `context` feature, which needs `std`, the lttng-ust 2.12 headers
+//! and the `helpers` feature of `lttng-ust-sys`. lttng-ust 2.13 isn't supported: combining
+//! `context` with the `v2_13` feature of `lttng-ust-sys` fails the build.
+
+use core::cell::{RefCell, UnsafeCell};
+use core::ffi::{c_char, c_void};
+use core::{fmt, mem, ptr};
+use std::boxed::Box;
+use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use lttng_ust_sys::context::*;
+use lttng_ust_sys::helpers::*;
+
+/// The value of a context for one event
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContextValue {
+    /// No value, e.g. outside of a request
+    None,
+    /// A signed integer
+    Signed(i64),
+    /// An unsigned integer
+    Unsigned(u64),
+    /// A floating point number
+    Float(f64),
+    /// A string. It is cut at the first NUL byte, if any.
+    String(String),
+}
+
+macro_rules! context_value_from {
+    ($($variant:ident($target:ty) <= $($source:ty),+;)*) => {
+        $($(
+            impl From<$source> for ContextValue {
+                fn from(value: $source) -> Self {
+                    ContextValue::$variant(<$target>::from(value))
+                }
+            }
+        )+)*
+    };
+}
+
+context_value_from! {
+    Signed(i64) <= i8, i16, i32, i64;
+    Unsigned(u64) <= u8, u16, u32, u64;
+    Float(f64) <= f32, f64;
+    String(String) <= String, &str;
+}
+
+impl<T: Into<ContextValue>> From<Option<T>> for ContextValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(ContextValue::None, Into::into)
+    }
+}
+
+impl ContextValue {
+    fn selector(&self) -> lttng_ust_dynamic_type {
+        match *self {
+            ContextValue::None => LTTNG_UST_DYNAMIC_TYPE_NONE,
+            ContextValue::Signed(_) => LTTNG_UST_DYNAMIC_TYPE_S64,
+            ContextValue::Unsigned(_) => LTTNG_UST_DYNAMIC_TYPE_U64,
+            ContextValue::Float(_) => LTTNG_UST_DYNAMIC_TYPE_DOUBLE,
+            ContextValue::String(_) => LTTNG_UST_DYNAMIC_TYPE_STRING,
+        }
+    }
+
+    /// The string, without its NUL terminator
+    fn text(s: &str) -> &[u8] {
+        let bytes = s.as_bytes();
+        &bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]
+    }
+
+    /// Lay the value out as a selector byte then the value, each aligned like the C types
+    /// they stand for. `get_size` measures this and `record` writes it, so they always agree.
+    fn lay_out<L: Layout>(&self, out: &mut L) {
+        out.align(1);
+        out.write(&[self.selector() as u8]);
+        let number = match *self {
+            ContextValue::None => return,
+            ContextValue::Signed(v) => v.to_ne_bytes(),
+            ContextValue::Unsigned(v) => v.to_ne_bytes(),
+            ContextValue::Float(v) => v.to_ne_bytes(),
+            ContextValue::String(ref s) => {
+                out.write(ContextValue::text(s));
+                out.write(b"\0");
+                return;
+            }
+        };
+        out.align(mem::align_of::<u64>());
+        out.write(&number);
+    }
+
+    /// Space taken at `offset` in a ring buffer which pads values as `padding` says
+    fn size(&self, offset: usize, padding: fn(usize, usize) -> usize) -> usize {
+        let mut measure = Measure { end: offset, padding };
+        self.lay_out(&mut measure);
+        measure.end - offset
+    }
+}
+
+/// Where a value is laid out
+trait Layout {
+    /// Pad the next write to a multiple of `alignment`, if the ring buffer is aligned
+    fn align(&mut self, alignment: usize);
+    fn write(&mut self, bytes: &[u8]);
+}
+
+/// Counts the bytes a value takes
+struct Measure {
+    end: usize,
+    /// Padding needed at an offset for an alignment
+    padding: fn(usize, usize) -> usize,
+}
+
+impl Layout for Measure {
+    fn align(&mut self, alignment: usize) {
+        self.end += (self.padding)(self.end, alignment);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.end += bytes.len();
+    }
+}
+
+fn ring_buffer_padding(offset: usize, alignment: usize) -> usize {
+    unsafe { lttng_ust_sys_ring_buffer_align(offset, alignment) }
+}
+
+/// The ring buffer position of the event being recorded
+struct RingBuffer {
+    ctx: *mut lttng_ust_lib_ring_buffer_ctx,
+    chan: *mut lttng_channel,
+}
+
+impl Layout for RingBuffer {
+    fn align(&mut self, alignment: usize) {
+        unsafe { lttng_ust_sys_ring_buffer_align_ctx(self.ctx, alignment) }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let src = bytes.as_ptr() as *const c_void;
+        unsafe { lttng_ust_sys_event_write(self.chan, self.ctx, src, bytes.len()) }
+    }
+}
+
+type Callback = dyn Fn() -> ContextValue + Send + Sync;
+
+/// Builds an application context provider, named `$app.<name>` on the lttng side.
+///
+/// Context providers only work with lttng-ust 2.12: the shim writing their values into the
+/// ring buffer is compiled against its headers, and enabling the `v2_13` feature of
+/// `lttng-ust-sys` along with the `context` feature fails the build.
+pub struct ContextProvider {
+    name: String,
+    contexts: Vec<(String, Box<Callback>)>,
+}
+
+impl ContextProvider {
+    /// A provider for the `$app.<name>:<context>` contexts
+    pub fn new(name: &str) -> Self {
+        ContextProvider {
+            name: name.into(),
+            contexts: Vec::new(),
+        }
+    }
+
+    /// Compute `$app.<provider>:<name>` with `value`. Replaces any previous closure for
+    /// the same name.
+    pub fn context<F>(mut self, name: &str, value: F) -> Self
+        where F: Fn() -> ContextValue + Send + Sync + 'static {
+        self.contexts.retain(|(existing, _)| existing != name);
+        self.contexts.push((name.into(), Box::new(value)));
+        self
+    }
+
+    /// Register the provider with lttng-ust. It stays registered until the returned value is
+    /// dropped.
+    pub fn register(self) -> Result<RegisteredProvider, Error> {
+        if self.name.is_empty() || self.name.contains([':', '\0']) {
+            return Err(Error::InvalidName(self.name));
+        }
+        if let Some((name, _)) = self.contexts.iter().find(|(name, _)| name.is_empty() || name.contains('\0')) {
+            return Err(Error::InvalidName(name.clone()));
+        }
+        let name = CString::new(format!("$app.{}", self.name)).expect("checked for NUL bytes above");
+        let provider = Arc::new(Provider {
+            raw: UnsafeCell::new(lttng_ust_context_provider {
+                name: name.as_ptr() as *mut c_char,
+                get_size: Some(get_size),
+                record: Some(record),
+                get_value: Some(get_value),
+                node: cds_hlist_node { next: ptr::null_mut(), pprev: ptr::null_mut() },
+            }),
+            name,
+            contexts: self.contexts,
+        });
+
+        // The callbacks may run as soon as lttng-ust knows about the provider. The lock isn't
+        // held while registering, since lttng-ust waits for callbacks that may be waiting on it.
+        PROVIDERS.lock().unwrap_or_else(|e| e.into_inner()).push(provider.clone());
+        GENERATION.fetch_add(1, Ordering::Release);
+        let ret = unsafe { lttng_ust_context_provider_register(provider.raw.get()) };
+        if ret != 0 {
+            PROVIDERS.lock().unwrap_or_else(|e| e.into_inner())
+                .retain(|other| !Arc::ptr_eq(other, &provider));
+            GENERATION.fetch_add(1, Ordering::Release);
+            return Err(Error::Registration(self.name, ret));
+        }
+        Ok(RegisteredProvider { provider })
+    }
+}
+
+impl fmt::Debug for ContextProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ContextProvider")
+            .field("name", &self.name)
+            .field("contexts", &self.contexts.iter().map(|(name, _)| name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// A registered context provider. Dropping it unregisters the provider, after which its
+/// contexts have no value.
+#[derive(Debug)]
+pub struct RegisteredProvider {
+    provider: Arc<Provider>,
+}
+
+impl Drop for RegisteredProvider {
+    fn drop(&mut self) {
+        // Waits for the callbacks running on other threads to return
+        unsafe { lttng_ust_context_provider_unregister(self.provider.raw.get()) };
+        PROVIDERS.lock().unwrap_or_else(|e| e.into_inner())
+            .retain(|provider| !Arc::ptr_eq(provider, &self.provider));
+        GENERATION.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Errors from [`ContextProvider::register`](struct.ContextProvider.html#method.register)
+#[derive(Debug)]
+pub enum Error {
+    /// The provider or one of its contexts (named by this) has an empty name or one with a NUL
+    /// byte, or the provider name contains a `:`
+    InvalidName(String),
+    /// lttng-ust refused the provider, with a negative errno value. This happens when a
+    /// provider of the same name is already registered.
+    Registration(String, i32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidName(ref name) =>
+                write!(f, "Invalid name for a context provider or context: {:?}", name),
+            Error::Registration(ref name, errno) =>
+                write!(f, "Failed to register context provider {:?} (error {})", name, errno),
+        }
+    }
+}
+
+impl ::std::error::Error for Error {}
+
+struct Provider {
+    /// Owned by lttng-ust while registered, which links it into its lists
+    raw: UnsafeCell<lttng_ust_context_provider>,
+    name: CString,
+    contexts: Vec<(String, Box<Callback>)>,
+}
+
+unsafe impl Send for Provider {}
+unsafe impl Sync for Provider {}
+
+impl fmt::Debug for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Provider").field("name", &self.name).finish()
+    }
+}
+
+impl Provider {
+    /// Whether `field_name` is `<provider>:<context>` for the context at `index`
+    fn names(&self, index: usize, field_name: &[u8]) -> bool {
+        let (provider, context) = (self.name.as_bytes(), self.contexts[index].0.as_bytes());
+        field_name.len() == provider.len() + 1 + context.len() && field_name.starts_with(provider)
+            && field_name[provider.len()] == b':' && field_name.ends_with(context)
+    }
+}
+
+/// The 2.12 callbacks only get the name of the context, so it is looked up here
+static PROVIDERS: Mutex<Vec<Arc<Provider>>> = Mutex::new(Vec::new());
+
+/// Bumped whenever `PROVIDERS` changes, which invalidates every `Lookup`
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Where the value of a field was found, in some generation of `PROVIDERS`
+#[derive(Clone, Copy)]
+struct Lookup {
+    field: usize,
+    generation: usize,
+    provider: *const Provider,
+    context: usize,
+}
+
+thread_local! {
+    /// Values computed by `get_size`, for `record` to write the same thing. An event that
+    /// doesn't fit in the ring buffer never gets to `record`, so an entry is replaced rather
+    /// than added on the next `get_size` for the same field.
+    static PENDING: RefCell<Vec<(usize, ContextValue)>> = const { RefCell::new(Vec::new()) };
+    /// NUL terminated strings handed to filters, which must outlive `get_value`. Each field's
+    /// buffer is reused by the next evaluation of the same field.
+    static FILTER_STRINGS: RefCell<Vec<(usize, Vec<u8>)>> = const { RefCell::new(Vec::new()) };
+    /// The fields this thread has evaluated, so events don't take the `PROVIDERS` lock
+    static LOOKUPS: RefCell<Vec<Lookup>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Find the provider and context `field` is named after, `None` if there is none
+unsafe fn lookup(field: *mut lttng_ctx_field, field_name: &[u8])
+                 -> Option<(*const Provider, usize)> {
+    let generation = GENERATION.load(Ordering::Acquire);
+    let cached = LOOKUPS.try_with(|lookups| {
+        let lookups = lookups.try_borrow().ok()?;
+        lookups.iter().find(|lookup| lookup.field == field as usize).copied()
+    });
+    // Providers of the current generation are still alive. The name is checked too, since
+    // lttng-ust may have reused the field for another context.
+    if let Ok(Some(lookup)) = cached {
+        if lookup.generation == generation && (*lookup.provider).names(lookup.context, field_name) {
+            return Some((lookup.provider, lookup.context));
+        }
+    }
+
+    let found = PROVIDERS.lock().unwrap_or_else(|e| e.into_inner()).iter()
+        .find_map(|provider| {
+            let context = (0..provider.contexts.len())
+                .find(|&index| provider.names(index, field_name))?;
+            Some((Arc::as_ptr(provider), context))
+        })?;
+    let (provider, context) = found;
+    let _ = LOOKUPS.try_with(|lookups| {
+        if let Ok(mut lookups) = lookups.try_borrow_mut() {
+            let field = field as usize;
+            lookups.retain(|lookup| lookup.field != field && lookup.generation == generation);
+            lookups.push(Lookup { field, generation, provider, context });
+        }
+    });
+    Some(found)
+}
+
+/// Compute the value of `field`, `None` if its provider or context is unknown or the
+/// closure panics
+unsafe fn evaluate(field: *mut lttng_ctx_field) -> ContextValue {
+    let field_name = CStr::from_ptr(lttng_ust_sys_ctx_field_name(field)).to_bytes();
+    let (provider, context) = match lookup(field, field_name) {
+        Some(found) => found,
+        None => return ContextValue::None,
+    };
+    // lttng-ust doesn't unregister a provider while its callbacks run, and a provider is only
+    // dropped once unregistered. Not holding the lock while the closure runs lets it fire
+    // events itself.
+    let provider = &*provider;
+    let value = &provider.contexts[context].1;
+    panic::catch_unwind(AssertUnwindSafe(value)).unwrap_or(ContextValue::None)
+}
+
+unsafe extern "C" fn get_size(field: *mut lttng_ctx_field, offset: usize) -> usize {
+    let value = evaluate(field);
+    let size = value.size(offset, ring_buffer_padding);
+    let _ = PENDING.try_with(|pending| {
+        if let Ok(mut pending) = pending.try_borrow_mut() {
+            pending.retain(|&(pending_field, _)| pending_field != field as usize);
+            pending.push((field as usize, value));
+        }
+    });
+    size
+}
+
+unsafe extern "C" fn record(field: *mut lttng_ctx_field, ctx: *mut lttng_ust_lib_ring_buffer_ctx,
+                            chan: *mut lttng_channel) {
+    let pending = PENDING.try_with(|pending| {
+        let mut pending = pending.try_borrow_mut().ok()?;
+        let index = pending.iter().position(|&(pending_field, _)| pending_field == field as usize)?;
+        Some(pending.swap_remove(index).1)
+    });
+    // Recomputing is a last resort, the value may not match the space reserved for it
+    let value = match pending {
+        Ok(Some(value)) => value,
+        _ => evaluate(field),
+    };
+    value.lay_out(&mut RingBuffer { ctx, chan });
+}
+
+unsafe extern "C" fn get_value(field: *mut lttng_ctx_field, value: *mut lttng_ctx_value) {
+    let computed = evaluate(field);
+    let value = &mut *value;
+    value.sel = computed.selector();
+    match computed {
+        ContextValue::None => {}
+        ContextValue::Signed(v) => value.u.s64 = v,
+        // 2.12 has no unsigned member, filters read the bits back as `u64`
+        ContextValue::Unsigned(v) => value.u.s64 = v as i64,
+        ContextValue::Float(v) => value.u.d = v,
+        ContextValue::String(s) => {
+            let stored = FILTER_STRINGS.try_with(|strings| {
+                let mut strings = strings.try_borrow_mut().ok()?;
+                let field = field as usize;
+                let index = match strings.iter().position(|&(other, _)| other == field) {
+                    Some(index) => index,
+                    None => {
+                        strings.push((field, Vec::new()));
+                        strings.len() - 1
+                    }
+                };
+                let text = &mut strings[index].1;
+                text.clear();
+                text.extend_from_slice(ContextValue::text(&s));
+                text.push(0);
+                Some(text.as_ptr() as *const c_char)
+            });
+            match stored {
+                Ok(Some(text)) => value.u.str = text,
+                _ => value.sel = LTTNG_UST_DYNAMIC_TYPE_NONE,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pads like lttng-ust where the ring buffer is aligned
+    fn natural(offset: usize, alignment: usize) -> usize {
+        (alignment - offset % alignment) % alignment
+    }
+
+    /// Pads like lttng-ust where unaligned accesses are cheap
+    fn packed(_offset: usize, _alignment: usize) -> usize {
+        0
+    }
+
+    /// A ring buffer with `offset` bytes already written
+    struct Buffer {
+        bytes: Vec<u8>,
+        padding: fn(usize, usize) -> usize,
+    }
+
+    impl Layout for Buffer {
+        fn align(&mut self, alignment: usize) {
+            let padded = self.bytes.len() + (self.padding)(self.bytes.len(), alignment);
+            self.bytes.resize(padded, 0xaa);
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.bytes.extend_from_slice(bytes);
+        }
+    }
+
+    fn recorded(value: &ContextValue, offset: usize, padding: fn(usize, usize) -> usize)
+                -> Vec<u8> {
+        let mut buffer = Buffer { bytes: vec![0x55; offset], padding };
+        value.lay_out(&mut buffer);
+        buffer.bytes.split_off(offset)
+    }
+
+    #[test]
+    fn size_matches_what_is_recorded() {
+        let values = [
+            ContextValue::None,
+            ContextValue::Signed(-3),
+            ContextValue::Unsigned(7),
+            ContextValue::Float(0.5),
+            ContextValue::from(""),
+            ContextValue::from("acme"),
+        ];
+        for value in values.iter() {
+            for offset in 0..16 {
+                for &padding in [natural as fn(usize, usize) -> usize, packed].iter() {
+                    assert_eq!(value.size(offset, padding), recorded(value, offset, padding).len(),
+                               "{:?} at {}", value, offset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn numbers_follow_an_aligned_selector() {
+        let value = ContextValue::Unsigned(0x0102_0304_0506_0708);
+        let mut expected = vec![LTTNG_UST_DYNAMIC_TYPE_U64 as u8, 0xaa, 0xaa, 0xaa, 0xaa];
+        expected.extend_from_slice(&0x0102_0304_0506_0708u64.to_ne_bytes());
+        assert_eq!(recorded(&value, 3, natural), expected);
+        assert_eq!(value.size(3, natural), 13);
+
+        let mut expected = vec![LTTNG_UST_DYNAMIC_TYPE_DOUBLE as u8];
+        expected.extend_from_slice(&1.5f64.to_ne_bytes());
+        assert_eq!(recorded(&ContextValue::Float(1.5), 3, packed), expected);
+        assert_eq!(recorded(&ContextValue::None, 5, natural), [LTTNG_UST_DYNAMIC_TYPE_NONE as u8]);
+    }
+
+    #[test]
+    fn invalid_names_are_reported() {
+        let invalid = |provider: ContextProvider| match provider.register() {
+            Err(Error::InvalidName(name)) => name,
+            Err(e) => panic!("expected InvalidName, got {}", e),
+            Ok(_) => panic!("expected InvalidName"),
+        };
+        let unset = || ContextValue::None;
+        assert_eq!(invalid(ContextProvider::new("my:app").context("id", unset)), "my:app");
+        assert_eq!(invalid(ContextProvider::new("").context("id", unset)), "");
+        assert_eq!(invalid(ContextProvider::new("app").context("id", unset).context("a\0b", unset)), "a\0b");
+        assert_eq!(invalid(ContextProvider::new("app").context("", unset)), "");
+    }
+
+    /// The helpers shim only builds against lttng-ust 2.12, which the build has to say
+    #[test]
+    fn refuses_to_build_against_lttng_ust_2_13() {
+        let target_dir = ::std::env::temp_dir().join(format!("lttng-ust-v2_13-{}", ::std::process::id()));
+        let output = ::std::process::Command::new(::std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+            .args(["check", "--quiet", "--features", "context,lttng-ust-sys/v2_13", "--manifest-path"])
+            .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .output()
+            .unwrap();
+        let _ = ::std::fs::remove_dir_all(&target_dir);
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("can't be combined with v2_13"));
+    }
+
+    #[test]
+    fn strings_are_cut_at_nul_and_terminated() {
+        let value = ContextValue::from("ab\0cd");
+        let expected = [LTTNG_UST_DYNAMIC_TYPE_STRING as u8, b'a', b'b', 0];
+        assert_eq!(recorded(&value, 1, natural), expected);
+        assert_eq!(value.size(1, natural), expected.len());
+    }
+}
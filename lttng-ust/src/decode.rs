@@ -0,0 +1,125 @@
+//! Helpers for programs reading back traces of tracepoints generated by `lttng-ust-generate`.
+//!
+//! Trace readers hand out the fields of an event the way the C side recorded them. An
+//! optional field (see `EventClass::add_optional_field`) is recorded as a `u8` presence flag
+//! followed by its value, which is zeroed when absent. [`optional`] puts such a pair back
+//! together, and [`Optionals`] does it for every field of an event.
+//!
+//! Which recorded fields are presence flags comes from the generator rather than from their
+//! names: schema files written by `lttng_ust_generate::schema::write` (and exported with
+//! `Generator::export`) follow every optional field with a `presence <flag>` statement, and
+//! [`FieldDescriptor::presence_flag`](::registry::FieldDescriptor::presence_flag) gives it
+//! for registered tracepoints.
+//!
+//! ```
+//! use lttng_ust::decode::Optionals;
+//!
+//! // As decoded from an event with a `user_id` field and an optional `retries` field, whose
+//! // schema says `presence retries_present`
+//! let flags = [("retries_present", "retries")];
+//! let recorded = [("user_id", 7u64), ("retries_present", 0), ("retries", 0)];
+//! let fields: Vec<_> = Optionals::new(recorded.iter().cloned(), &flags, |&flag| flag != 0).collect();
+//! assert_eq!(fields, [("user_id", Some(7)), ("retries", None)]);
+//! ```
+//!
+//! Serde fields (see `EventClass::add_serde_field`) are recorded as a byte sequence named
+//! after their encoding, `<name>_cbor` or `<name>_bincode`. They can't be optional.
+
+/// Appended to the name of fields recorded as several values, e.g. `u128` and `SocketAddr`
+/// fields, to name each of the values. Mirrors `CTFType::lowered` in `lttng-ust-generate`.
+const PART_SUFFIXES: &[&str] = &["", "_hi", "_lo", "_port"];
+
+/// Rebuild the `Option` an optional field was recorded from, given the value of its presence
+/// flag and the value (or tuple of values) recorded after it.
+pub fn optional<T>(present: u8, value: T) -> Option<T> {
+    if present != 0 {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// An iterator over the recorded `(name, value)` pairs of an event that folds the presence
+/// flag of every optional field into the value after it.
+///
+/// Every recorded field other than the flags is yielded once, in order: as `Some(value)`
+/// unless it is (part of) an optional field whose flag was clear.
+pub struct Optionals<'a, I: Iterator, F> {
+    fields: I,
+    /// `(flag, field)` for every optional field of the event
+    flags: &'a [(&'a str, &'a str)],
+    is_set: F,
+    /// The optional field whose parts are being yielded, and whether it is present
+    current: Option<(&'a str, bool)>,
+}
+
+impl<'a, V, I, F> Optionals<'a, I, F>
+    where I: Iterator<Item = (&'a str, V)>, F: FnMut(&V) -> bool
+{
+    /// Wrap the recorded fields of an event. `flags` pairs the presence flag of each optional
+    /// field with the field's name, and `is_set` tells whether the value of a flag is nonzero.
+    pub fn new(fields: I, flags: &'a [(&'a str, &'a str)], is_set: F) -> Self {
+        Optionals { fields, flags, is_set, current: None }
+    }
+}
+
+impl<'a, V, I, F> Iterator for Optionals<'a, I, F>
+    where I: Iterator<Item = (&'a str, V)>, F: FnMut(&V) -> bool
+{
+    type Item = (&'a str, Option<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (name, value) = self.fields.next()?;
+            if let Some((optional_name, present)) = self.current {
+                if is_part_of(name, optional_name) {
+                    return Some((name, if present { Some(value) } else { None }));
+                }
+                self.current = None;
+            }
+            match self.flags.iter().find(|&&(flag, _)| flag == name) {
+                Some(&(_, field)) => self.current = Some((field, (self.is_set)(&value))),
+                None => return Some((name, Some(value))),
+            }
+        }
+    }
+}
+
+/// Whether the recorded field `name` holds (part of) the value of field `field`
+fn is_part_of(name: &str, field: &str) -> bool {
+    name.strip_prefix(field).is_some_and(|suffix| PART_SUFFIXES.contains(&suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold<'a>(recorded: &[(&'a str, u64)], flags: &'a [(&'a str, &'a str)])
+                -> ::std::vec::Vec<(&'a str, Option<u64>)> {
+        Optionals::new(recorded.iter().cloned(), flags, |&flag| flag != 0).collect()
+    }
+
+    #[test]
+    fn pairs_flags_with_their_values() {
+        assert_eq!(optional(1, 5), Some(5));
+        assert_eq!(optional(0, 5), None);
+        assert_eq!(fold(&[("a_present", 1), ("a", 3), ("b_present", 0), ("b", 0), ("c", 9)],
+                        &[("a_present", "a"), ("b_present", "b")]),
+                   [("a", Some(3)), ("b", None), ("c", Some(9))]);
+    }
+
+    #[test]
+    fn covers_every_part_of_split_values() {
+        assert_eq!(fold(&[("big_present", 0), ("big_hi", 0), ("big_lo", 0), ("peer_present", 1),
+                          ("peer", 1), ("peer_port", 80), ("big_lo", 4)],
+                        &[("big_present", "big"), ("peer_present", "peer")]),
+                   [("big_hi", None), ("big_lo", None), ("peer", Some(1)), ("peer_port", Some(80)),
+                    ("big_lo", Some(4))]);
+    }
+
+    #[test]
+    fn only_folds_the_flags_it_is_given() {
+        assert_eq!(fold(&[("x_present", 1), ("x", 2), ("y_present", 0), ("y", 3)], &[("y_present", "y")]),
+                   [("x_present", Some(1)), ("x", Some(2)), ("y", None)]);
+    }
+}
@@ -1,7 +1,30 @@
 //! # Runtime support for `lttng-ust-rs`.
-//! Currently only exports a macro to make importing your tracepoints more convenient.
+//! Exports a macro to make importing your tracepoints more convenient, and the
+//! [`registry`](registry/index.html) of the tracepoints compiled into the program.
+//! With the `tracef` feature, [`tracef!`](macro.tracef.html) and
+//! [`tracelog!`](macro.tracelog.html) record ad-hoc events without any generated code.
+//! [`decode`](decode/index.html) helps programs reading traces put optional fields back
+//! together. With the `context` feature, [`context`](context/index.html) adds values
+//! computed by closures to every event of a session.
+//!
+//! Apart from the `context` feature, this crate doesn't depend on `std`, so it can be used
+//! from `#![no_std]` crates along with bindings generated with `Generator::no_std`.
+#![no_std]
 #![deny(missing_docs)]
 
+#[cfg(any(feature = "tracef", feature = "context"))]
+extern crate lttng_ust_sys;
+#[cfg(any(test, feature = "context"))]
+#[macro_use]
+extern crate std;
+
+#[cfg(feature = "context")]
+pub mod context;
+pub mod decode;
+pub mod registry;
+#[cfg(feature = "tracef")]
+pub mod tracef;
+
 /// Imports tracepoints. See the module documentation for `lttng-ust-generate` or the `examples` folder in
 /// [the repo](https://github.com/bobtwinkles/lttng-ust-rs/tree/master/examples)
 /// for an examples of how to use this macro.
@@ -0,0 +1,287 @@
+//! A process-wide list of the tracepoints compiled into the program.
+//!
+//! Bindings generated with `Generator::runtime_registry` register a descriptor for each of
+//! their tracepoints when the program starts, which can then be listed with [`events`]:
+//!
+//! ```no_run
+//! for event in lttng_ust::registry::events() {
+//!     println!("{}:{} ({:?}, enabled: {})",
+//!              event.provider(), event.name(), event.level(), event.is_enabled());
+//! }
+//! ```
+//!
+//! Tracepoints compiled out by `Generator::max_level` aren't registered.
+
+use core::iter::FusedIterator;
+use core::ptr;
+use core::slice;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+/// The log level of a tracepoint, mirroring `lttng_ust_generate::LogLevel`.
+/// Levels are ordered from most to least severe, so `LogLevel::Error < LogLevel::Info`.
+#[derive(Copy,Clone,PartialEq,Eq,PartialOrd,Ord,Hash,Debug)]
+pub enum LogLevel {
+    /// Corresponds to the `TRACE_EMERG` log level
+    Emergency,
+    /// Corresponds to the `TRACE_ALERT` log level
+    Alert,
+    /// Corresponds to the `TRACE_CRIT` log level
+    Critical,
+    /// Corresponds to the `TRACE_ERR` log level
+    Error,
+    /// Corresponds to the `TRACE_WARNING` log level
+    Warning,
+    /// Corresponds to the `TRACE_NOTICE` log level
+    Notice,
+    /// Corresponds to the `TRACE_INFO` log level
+    Info,
+    /// Corresponds to the `TRACE_DEBUG_SYSTEM` log level
+    DebugSystem,
+    /// Corresponds to the `TRACE_DEBUG_PROGRAM` log level
+    DebugProgram,
+    /// Corresponds to the `TRACE_DEBUG_PROCESS` log level
+    DebugProcess,
+    /// Corresponds to the `TRACE_DEBUG_MODULE` log level
+    DebugModule,
+    /// Corresponds to the `TRACE_DEBUG_UNIT` log level
+    DebugUnit,
+    /// Corresponds to the `TRACE_DEBUG_FUNCTION` log level
+    DebugFunction,
+    /// Corresponds to the `TRACE_DEBUG_LINE` log level
+    DebugLine,
+    /// Corresponds to the `TRACE_DEBUG` log level
+    Debug,
+}
+
+/// A field of a registered tracepoint
+#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+pub struct FieldDescriptor {
+    name: &'static str,
+    ty: &'static str,
+    presence: Option<&'static str>,
+}
+
+impl FieldDescriptor {
+    #[doc(hidden)]
+    pub const fn new(name: &'static str, ty: &'static str, presence: Option<&'static str>) -> Self {
+        FieldDescriptor { name, ty, presence }
+    }
+
+    /// The name of the field, as passed to `EventClass::add_field`
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The type of the field, spelled like in `lttng_ust_generate` schema files
+    /// (e.g. `integer u32` or `optional sequence_text`)
+    pub fn type_name(&self) -> &'static str {
+        self.ty
+    }
+
+    /// The `u8` flag recorded in front of an optional field, telling whether it is present.
+    /// `None` if the field isn't optional. See [`decode`](::decode).
+    pub fn presence_flag(&self) -> Option<&'static str> {
+        self.presence
+    }
+}
+
+/// A registered tracepoint
+#[derive(Copy,Clone)]
+pub struct EventDescriptor {
+    provider: &'static str,
+    class: &'static str,
+    name: &'static str,
+    level: LogLevel,
+    fields: &'static [FieldDescriptor],
+    enabled: fn() -> bool,
+}
+
+impl EventDescriptor {
+    #[doc(hidden)]
+    pub const fn new(provider: &'static str, class: &'static str, name: &'static str,
+                     level: LogLevel, fields: &'static [FieldDescriptor],
+                     enabled: fn() -> bool) -> Self {
+        EventDescriptor { provider, class, name, level, fields, enabled }
+    }
+
+    /// The name of the provider the tracepoint belongs to
+    pub fn provider(&self) -> &'static str {
+        self.provider
+    }
+
+    /// The name of the event class the tracepoint instantiates
+    pub fn class(&self) -> &'static str {
+        self.class
+    }
+
+    /// The name of the tracepoint, which is what `lttng enable-event -u <provider>:<name>`
+    /// expects after the provider
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The log level of the tracepoint
+    pub fn level(&self) -> LogLevel {
+        self.level
+    }
+
+    /// The fields recorded by the tracepoint, in order
+    pub fn fields(&self) -> &'static [FieldDescriptor] {
+        self.fields
+    }
+
+    /// Whether any tracing session is currently recording the tracepoint
+    pub fn is_enabled(&self) -> bool {
+        (self.enabled)()
+    }
+}
+
+impl ::core::fmt::Debug for EventDescriptor {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+        f.debug_struct("EventDescriptor")
+            .field("provider", &self.provider)
+            .field("class", &self.class)
+            .field("name", &self.name)
+            .field("level", &self.level)
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+/// The tracepoints of one set of generated bindings, linked into the registry
+#[doc(hidden)]
+pub struct Registration {
+    events: &'static [EventDescriptor],
+    registered: AtomicBool,
+    next: AtomicPtr<Registration>,
+}
+
+impl Registration {
+    #[doc(hidden)]
+    pub const fn new(events: &'static [EventDescriptor]) -> Self {
+        Registration {
+            events,
+            registered: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+static HEAD: AtomicPtr<Registration> = AtomicPtr::new(ptr::null_mut());
+
+/// Add `registration` to the registry. Generated bindings call this at startup; registering
+/// the same bindings again does nothing.
+#[doc(hidden)]
+pub fn register(registration: &'static Registration) {
+    if registration.registered.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let node = registration as *const Registration as *mut Registration;
+    let mut head = HEAD.load(Ordering::Acquire);
+    loop {
+        registration.next.store(head, Ordering::Relaxed);
+        match HEAD.compare_exchange_weak(head, node, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return,
+            Err(current) => head = current,
+        }
+    }
+}
+
+/// Every registered tracepoint. Bindings registered later come first.
+pub fn events() -> Events {
+    Events {
+        current: [].iter(),
+        next: HEAD.load(Ordering::Acquire),
+    }
+}
+
+/// Iterator returned by [`events`]
+#[derive(Clone,Debug)]
+pub struct Events {
+    current: slice::Iter<'static, EventDescriptor>,
+    next: *const Registration,
+}
+
+impl Iterator for Events {
+    type Item = &'static EventDescriptor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.current.next() {
+                return Some(event);
+            }
+            // Registrations are 'static and never unlinked
+            let registration: &'static Registration = unsafe { self.next.as_ref()? };
+            self.current = registration.events.iter();
+            self.next = registration.next.load(Ordering::Acquire);
+        }
+    }
+}
+
+impl FusedIterator for Events {}
+
+// Only ever points to 'static registrations, which are Sync
+unsafe impl Send for Events {}
+unsafe impl Sync for Events {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> bool {
+        true
+    }
+
+    fn disabled() -> bool {
+        false
+    }
+
+    static FIELDS: [FieldDescriptor; 2] = [
+        FieldDescriptor::new("size", "integer u32", None),
+        FieldDescriptor::new("retries", "optional integer u8", Some("retries_present")),
+    ];
+
+    // Registered at startup, the way generated bindings do it
+    static STARTUP_EVENTS: [EventDescriptor; 1] = [
+        EventDescriptor::new("p", "c", "at_startup", LogLevel::Info, &FIELDS, enabled),
+    ];
+    static STARTUP: Registration = Registration::new(&STARTUP_EVENTS);
+
+    extern "C" fn register_startup() {
+        register(&STARTUP);
+    }
+
+    #[used]
+    #[cfg_attr(target_os = "linux", unsafe(link_section = ".init_array"))]
+    static REGISTER: extern "C" fn() = register_startup;
+
+    static LATE_EVENTS: [EventDescriptor; 2] = [
+        EventDescriptor::new("p", "c", "late", LogLevel::Debug, &FIELDS, disabled),
+        EventDescriptor::new("q", "d", "later", LogLevel::Error, &[], enabled),
+    ];
+    static LATE: Registration = Registration::new(&LATE_EVENTS);
+
+    fn names() -> ::std::vec::Vec<&'static str> {
+        events().map(|event| event.name()).collect()
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn lists_events_registered_at_startup() {
+        let event = events().find(|event| event.name() == "at_startup").unwrap();
+        assert_eq!((event.provider(), event.class(), event.level()), ("p", "c", LogLevel::Info));
+        assert_eq!(event.fields(), &FIELDS);
+        assert!(event.is_enabled());
+    }
+
+    #[test]
+    fn lists_each_registration_once() {
+        register(&LATE);
+        register(&LATE);
+        let names = names();
+        assert_eq!(names.iter().filter(|&&name| name == "late").count(), 1);
+        let late = names.iter().position(|&name| name == "late").unwrap();
+        assert_eq!(names[late + 1], "later");
+        assert!(!events().find(|event| event.name() == "late").unwrap().is_enabled());
+    }
+}
@@ -0,0 +1,167 @@
+//! Support for [`tracef!`](../macro.tracef.html) and [`tracelog!`](../macro.tracelog.html),
+//! which fire the tracepoints liblttng-ust provides for ad-hoc, printf-style events. They
+//! need no build-time generation, which makes them handy for quick debugging:
+//!
+//! ```text
+//! lttng enable-event -u 'lttng_ust_tracef:*'
+//! lttng enable-event -u 'lttng_ust_tracelog:*' --loglevel=TRACE_WARNING
+//! ```
+//!
+//! Only available with the `tracef` feature, which links `liblttng-ust` through
+//! `lttng-ust-sys`.
+
+use core::ffi::{c_char, c_int};
+use core::fmt::{self, Write};
+
+use lttng_ust_sys::tracef::*;
+use lttng_ust_sys::tracepoint::{tracepoint, tracepoint_enabled};
+use registry::LogLevel;
+
+macro_rules! tracelog_levels {
+    ($($level:ident => $tracepoint:ident, $func:ident;)*) => {
+        fn tracelog_state(level: LogLevel) -> &'static tracepoint {
+            unsafe {
+                match level {
+                    $(LogLevel::$level => &$tracepoint,)*
+                }
+            }
+        }
+
+        fn tracelog_func(level: LogLevel) -> unsafe extern "C" fn(*const c_char, c_int, *const c_char, *const c_char, ...) {
+            match level {
+                $(LogLevel::$level => $func,)*
+            }
+        }
+    };
+}
+
+tracelog_levels! {
+    Emergency => __tracepoint_lttng_ust_tracelog___TRACE_EMERG, _lttng_ust_tracelog_TRACE_EMERG;
+    Alert => __tracepoint_lttng_ust_tracelog___TRACE_ALERT, _lttng_ust_tracelog_TRACE_ALERT;
+    Critical => __tracepoint_lttng_ust_tracelog___TRACE_CRIT, _lttng_ust_tracelog_TRACE_CRIT;
+    Error => __tracepoint_lttng_ust_tracelog___TRACE_ERR, _lttng_ust_tracelog_TRACE_ERR;
+    Warning => __tracepoint_lttng_ust_tracelog___TRACE_WARNING, _lttng_ust_tracelog_TRACE_WARNING;
+    Notice => __tracepoint_lttng_ust_tracelog___TRACE_NOTICE, _lttng_ust_tracelog_TRACE_NOTICE;
+    Info => __tracepoint_lttng_ust_tracelog___TRACE_INFO, _lttng_ust_tracelog_TRACE_INFO;
+    DebugSystem => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_SYSTEM, _lttng_ust_tracelog_TRACE_DEBUG_SYSTEM;
+    DebugProgram => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_PROGRAM, _lttng_ust_tracelog_TRACE_DEBUG_PROGRAM;
+    DebugProcess => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_PROCESS, _lttng_ust_tracelog_TRACE_DEBUG_PROCESS;
+    DebugModule => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_MODULE, _lttng_ust_tracelog_TRACE_DEBUG_MODULE;
+    DebugUnit => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_UNIT, _lttng_ust_tracelog_TRACE_DEBUG_UNIT;
+    DebugFunction => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_FUNCTION, _lttng_ust_tracelog_TRACE_DEBUG_FUNCTION;
+    DebugLine => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG_LINE, _lttng_ust_tracelog_TRACE_DEBUG_LINE;
+    Debug => __tracepoint_lttng_ust_tracelog___TRACE_DEBUG, _lttng_ust_tracelog_TRACE_DEBUG;
+}
+
+/// Whether a tracing session records `lttng_ust_tracef:*` events
+pub fn tracef_enabled() -> bool {
+    unsafe { tracepoint_enabled(&__tracepoint_lttng_ust_tracef___event) }
+}
+
+/// Whether a tracing session records `lttng_ust_tracelog:*` events of `level`
+pub fn tracelog_enabled(level: LogLevel) -> bool {
+    unsafe { tracepoint_enabled(tracelog_state(level)) }
+}
+
+/// Messages are formatted on the stack, since this crate doesn't allocate. Longer ones get
+/// truncated.
+const CAPACITY: usize = 1024;
+
+struct Buffer {
+    bytes: [u8; CAPACITY],
+    len: usize,
+}
+
+impl Buffer {
+    fn format(args: fmt::Arguments) -> Self {
+        let mut buffer = Buffer { bytes: [0; CAPACITY], len: 0 };
+        let _ = buffer.write_fmt(args);
+        buffer
+    }
+}
+
+impl Write for Buffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut take = s.len().min(CAPACITY - self.len);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        self.bytes[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        Ok(())
+    }
+}
+
+/// The message is passed through `%.*s`, so it doesn't need a NUL terminator and `%` in it
+/// is harmless
+const MESSAGE_FORMAT: &[u8] = b"%.*s\0";
+
+#[doc(hidden)]
+pub fn tracef(args: fmt::Arguments) {
+    let message = Buffer::format(args);
+    unsafe {
+        _lttng_ust_tracef(MESSAGE_FORMAT.as_ptr() as *const c_char,
+                          message.len as c_int, message.bytes.as_ptr());
+    }
+}
+
+/// `file` and `module` must be NUL terminated
+#[doc(hidden)]
+pub fn tracelog(level: LogLevel, file: &'static str, line: u32, module: &'static str, args: fmt::Arguments) {
+    let message = Buffer::format(args);
+    unsafe {
+        tracelog_func(level)(file.as_ptr() as *const c_char, line as c_int, module.as_ptr() as *const c_char,
+                             MESSAGE_FORMAT.as_ptr() as *const c_char,
+                             message.len as c_int, message.bytes.as_ptr());
+    }
+}
+
+/// Record a `lttng_ust_tracef:event` event with a message formatted like `format!`.
+/// The message is only formatted when a tracing session records these events, and gets
+/// truncated to 1024 bytes.
+///
+/// ```no_run
+/// # #[macro_use] extern crate lttng_ust;
+/// # fn main() {
+/// let retries = 3;
+/// tracef!("giving up after {} retries", retries);
+/// # }
+/// ```
+///
+/// Requires the `tracef` feature, which links `liblttng-ust`.
+#[macro_export]
+macro_rules! tracef {
+    ($($arg:tt)+) => {
+        if $crate::tracef::tracef_enabled() {
+            $crate::tracef::tracef(format_args!($($arg)+));
+        }
+    };
+}
+
+/// Record a `lttng_ust_tracelog:<level>` event, like [`tracef!`](macro.tracef.html) but with a
+/// [`LogLevel`](registry/enum.LogLevel.html) and the source location, which makes it possible
+/// to filter on it with `lttng enable-event --loglevel`. The module path stands in for the C
+/// function name.
+///
+/// ```no_run
+/// # #[macro_use] extern crate lttng_ust;
+/// use lttng_ust::registry::LogLevel;
+/// # fn main() {
+/// # let path = "";
+/// tracelog!(LogLevel::Warning, "cache miss for {}", path);
+/// # }
+/// ```
+///
+/// Requires the `tracef` feature, which links `liblttng-ust`.
+#[macro_export]
+macro_rules! tracelog {
+    ($level:expr, $($arg:tt)+) => {
+        {
+            let level = $level;
+            if $crate::tracef::tracelog_enabled(level) {
+                $crate::tracef::tracelog(level, concat!(file!(), "\0"), line!(),
+                                         concat!(module_path!(), "\0"), format_args!($($arg)+));
+            }
+        }
+    };
+}